@@ -0,0 +1,181 @@
+//! Content-defined chunking (CDC) matching mode.
+//!
+//! The seed-grid algorithms (`greedy`, `onepass`, `correcting`) hash every
+//! `p`-byte-aligned block of R. That grid is shift-sensitive: a single-byte
+//! insertion near the start of R displaces every downstream block boundary,
+//! so every Copy after the edit point is lost even though almost all of R
+//! reappears unchanged in V. Content-defined chunking places boundaries at
+//! offsets determined by local content instead of position, using the
+//! existing Karp-Rabin rolling hash (`hash::RollingHash`) as the boundary
+//! test: a boundary falls wherever the low bits of the hash of the last
+//! `min` bytes equal zero. Since the boundary only depends on bytes near
+//! it, an edit only perturbs the one or two chunks it actually touches.
+//!
+//! `diff_cdc` is a self-contained entry point selected by
+//! `DiffOptions::chunking = Chunking::Rabin { .. }` (see `algorithm::diff`);
+//! it does not reuse the seed-grid algorithms' retain-existing hash tables
+//! since chunk boundaries here are content-aligned rather than fixed-stride.
+
+use std::collections::HashMap;
+
+use crate::hash::{fingerprint, RollingHash};
+use crate::types::{Chunking, Command, DiffOptions};
+
+/// Cut `data` into content-defined chunks, each in `[min, max]` bytes,
+/// boundary chosen where the rolling hash of the trailing `min`-byte window
+/// has its low `log2(avg)` bits all zero.
+fn chunk_boundaries(data: &[u8], min: usize, avg: usize, max: usize) -> Vec<(usize, usize)> {
+    let window = min.max(1);
+    let max = max.max(window);
+    let mask = avg.max(2).next_power_of_two() as u64 - 1;
+
+    let mut chunks = Vec::new();
+    let mut start = 0usize;
+    while start < data.len() {
+        let end_cap = (start + max).min(data.len());
+        let min_cut = (start + window).min(end_cap);
+
+        let mut cut = end_cap;
+        if min_cut < end_cap {
+            let mut rh = RollingHash::new(data, min_cut - window, window);
+            let mut pos = min_cut;
+            loop {
+                if rh.value() & mask == 0 {
+                    cut = pos;
+                    break;
+                }
+                if pos >= end_cap {
+                    break;
+                }
+                rh.roll(data[pos - window], data[pos]);
+                pos += 1;
+            }
+        }
+
+        chunks.push((start, cut));
+        start = cut;
+    }
+    chunks
+}
+
+/// Content-defined-chunking differencing: cuts R and V independently into
+/// content-aligned chunks and emits a `Copy` for every V chunk whose
+/// fingerprint (and full content) matches some R chunk, `Add` otherwise.
+///
+/// Falls back to a single fixed-size `(p, p, 4p)` chunking if
+/// `opts.chunking` is `Chunking::Fixed` — callers normally reach this
+/// function only via `algorithm::diff` when `Chunking::Rabin` is set.
+pub fn diff_cdc(r: &[u8], v: &[u8], opts: &DiffOptions) -> Vec<Command> {
+    let mut commands = Vec::new();
+    if v.is_empty() {
+        return commands;
+    }
+
+    let (min, avg, max) = match opts.chunking {
+        Chunking::Rabin { min, avg, max } => (min.max(1), avg.max(1), max.max(min.max(1))),
+        Chunking::Fixed => (opts.p, opts.p, opts.p.saturating_mul(4).max(opts.p)),
+    };
+
+    // Retain-existing policy, as in the seed-grid tables: the first R chunk
+    // with a given fingerprint wins; later collisions are ignored.
+    let mut by_fp: HashMap<u64, (usize, usize)> = HashMap::new();
+    for (s, e) in chunk_boundaries(r, min, avg, max) {
+        let fp = fingerprint(r, s, e - s);
+        by_fp.entry(fp).or_insert((s, e - s));
+    }
+
+    let mut add_start: Option<usize> = None;
+    for (s, e) in chunk_boundaries(v, min, avg, max) {
+        let len = e - s;
+        let fp = fingerprint(v, s, len);
+        let matched = by_fp
+            .get(&fp)
+            .filter(|&&(r_off, r_len)| r_len == len && r[r_off..r_off + len] == v[s..e]);
+
+        if let Some(&(r_off, _)) = matched {
+            if let Some(add_from) = add_start.take() {
+                commands.push(Command::Add { data: v[add_from..s].to_vec() });
+            }
+            commands.push(Command::Copy { offset: r_off, length: len });
+        } else if add_start.is_none() {
+            add_start = Some(s);
+        }
+    }
+    if let Some(add_from) = add_start {
+        commands.push(Command::Add { data: v[add_from..].to_vec() });
+    }
+
+    super::runify(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply_delta;
+
+    fn cdc_opts(min: usize, avg: usize, max: usize) -> DiffOptions {
+        DiffOptions { chunking: Chunking::Rabin { min, avg, max }, ..DiffOptions::default() }
+    }
+
+    #[test]
+    fn test_cdc_roundtrip_identical() {
+        let r = b"the quick brown fox jumps over the lazy dog, again and again".to_vec();
+        let cmds = diff_cdc(&r, &r, &cdc_opts(4, 16, 64));
+        assert_eq!(apply_delta(&r, &cmds), r);
+    }
+
+    #[test]
+    fn test_cdc_roundtrip_single_byte_insertion() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(7);
+        let r: Vec<u8> = (0..4000).map(|_| rng.gen()).collect();
+        let mut v = r.clone();
+        v.insert(5, 0xAB); // a single shift near the start
+        let cmds = diff_cdc(&r, &v, &cdc_opts(8, 32, 128));
+        assert_eq!(apply_delta(&r, &cmds), v);
+    }
+
+    #[test]
+    fn test_cdc_keeps_most_of_tail_after_early_insertion() {
+        // A single early insertion should leave most of the tail matchable
+        // under CDC, unlike a fixed p-byte grid which would lose it all.
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(11);
+        let r: Vec<u8> = (0..8000).map(|_| rng.gen()).collect();
+        let mut v = r.clone();
+        v.insert(3, 0x42);
+        let cmds = diff_cdc(&r, &v, &cdc_opts(16, 64, 256));
+        let copied: usize = cmds
+            .iter()
+            .map(|c| match c {
+                Command::Copy { length, .. } | Command::CopyOut { length, .. } => *length,
+                Command::Add { .. } | Command::Run { .. } => 0,
+            })
+            .sum();
+        assert!(copied > r.len() / 2, "expected most of R to still be copyable, got {copied}");
+        assert_eq!(apply_delta(&r, &cmds), v);
+    }
+
+    #[test]
+    fn test_cdc_empty_version() {
+        let cmds = diff_cdc(b"reference", b"", &cdc_opts(2, 4, 16));
+        assert!(cmds.is_empty());
+    }
+
+    #[test]
+    fn test_cdc_scattered_single_byte_insertions() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(42);
+        let r: Vec<u8> = (0..2000).map(|_| rng.gen()).collect();
+        let mut v = r.clone();
+        for _ in 0..20 {
+            let idx = rng.gen_range(0..v.len());
+            v.insert(idx, rng.gen());
+        }
+        let cmds = diff_cdc(&r, &v, &cdc_opts(8, 32, 128));
+        assert_eq!(apply_delta(&r, &cmds), v);
+    }
+}
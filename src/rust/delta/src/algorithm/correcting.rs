@@ -1,8 +1,11 @@
 use std::collections::VecDeque;
+use std::io::Read;
 
 use crate::hash::{fingerprint, next_prime};
 use crate::splay::SplayTree;
-use crate::types::{Command, SEED_LEN, TABLE_SIZE};
+use crate::types::{Command, DiffOptions};
+
+use super::{extend_backward_chunked, extend_forward_chunked};
 
 /// Internal buffer entry tracking which region of V a command encodes.
 struct BufEntry {
@@ -12,6 +15,96 @@ struct BufEntry {
     dummy: bool,
 }
 
+/// Checkpointing parameters shared by the in-memory and streaming entry
+/// points (Section 8.1, pp. 347-348): `cap` = |C|, `f_size` = |F|, `m` =
+/// checkpoint spacing, `k` = checkpoint class.
+///
+/// `k_fp` is the fingerprint of a representative V seed used to bias `k`
+/// (Eq. 3, p. 348); pass `None` when no such seed is available (the
+/// streaming path doesn't know V's length up front) to fall back to `k = 0`.
+struct CheckpointParams {
+    cap: usize,
+    f_size: u64,
+    m: u64,
+    k: u64,
+}
+
+fn checkpoint_params(num_seeds: usize, p: usize, q: usize, max_table: usize, k_fp: Option<u64>) -> CheckpointParams {
+    let cap = if num_seeds > 0 {
+        next_prime(q.max(2 * num_seeds / p).min(max_table))
+    } else {
+        next_prime(q)
+    };
+    let f_size: u64 = if num_seeds > 0 { next_prime(2 * num_seeds) as u64 } else { 1 };
+    let m: u64 = if f_size <= cap as u64 {
+        1
+    } else {
+        (f_size + cap as u64 - 1) / cap as u64
+    };
+    let k = match k_fp {
+        Some(fp) => fp % f_size % m,
+        None => 0,
+    };
+    CheckpointParams { cap, f_size, m, k }
+}
+
+/// A checkpoint slot's bounded-bucket contents: up to `bucket_k`
+/// `(full_fp, offset)` pairs (see `DiffOptions::bucket_k`).
+type Bucket = Vec<(u64, usize)>;
+
+/// Returns `(passed, stored, skipped_collision)` build counters alongside
+/// the two table representations.
+struct RTable {
+    h_r_ht: Vec<Bucket>,
+    h_r_sp: SplayTree<Bucket>,
+    passed: usize,
+    stored: usize,
+    skipped_collision: usize,
+}
+
+/// Step 1: build R's bounded-bucket checkpoint table (see `DiffOptions::bucket_k`).
+fn build_r_table(r: &[u8], p: usize, cp: &CheckpointParams, bucket_k: usize, use_splay: bool) -> RTable {
+    let mut h_r_ht: Vec<Bucket> = if !use_splay { vec![Vec::new(); cp.cap] } else { Vec::new() };
+    let mut h_r_sp: SplayTree<Bucket> = SplayTree::new();
+    let num_seeds = if r.len() >= p { r.len() - p + 1 } else { 0 };
+    let mut passed = 0;
+    let mut stored = 0;
+    let mut skipped_collision = 0;
+
+    for a in 0..num_seeds {
+        let fp = fingerprint(r, a, p);
+        let f = fp % cp.f_size;
+        if f % cp.m != cp.k {
+            continue;
+        }
+        passed += 1;
+
+        if use_splay {
+            let bucket = h_r_sp.insert_or_get(fp, Vec::new());
+            if bucket.len() < bucket_k {
+                bucket.push((fp, a));
+                stored += 1;
+            } else {
+                skipped_collision += 1;
+            }
+        } else {
+            let i = (f / cp.m) as usize;
+            if i >= cp.cap {
+                continue;
+            }
+            let bucket = &mut h_r_ht[i];
+            if bucket.len() < bucket_k {
+                bucket.push((fp, a));
+                stored += 1;
+            } else {
+                skipped_collision += 1;
+            }
+        }
+    }
+
+    RTable { h_r_ht, h_r_sp, passed, stored, skipped_collision }
+}
+
 /// Correcting 1.5-Pass algorithm (Section 7, Figure 8) with
 /// fingerprint-based checkpointing (Section 8).
 ///
@@ -37,47 +130,25 @@ struct BufEntry {
 /// Step 6: encode with tail correction via lookback buffer (Section 5.1).
 /// Backward extension (Section 8.2, p. 349) recovers true match starts
 /// that fall between checkpoint positions.
-pub fn diff_correcting(
-    r: &[u8],
-    v: &[u8],
-    p: usize,
-    q: usize,
-    buf_cap: usize,
-    verbose: bool,
-    use_splay: bool,
-    min_copy: usize,
-) -> Vec<Command> {
+pub fn diff_correcting(r: &[u8], v: &[u8], opts: &DiffOptions) -> Vec<Command> {
+    let p = opts.p;
+    let q = opts.q;
+    let buf_cap = opts.buf_cap;
+    let verbose = opts.verbose;
+    let use_splay = opts.use_splay;
+    let use_output_copy = opts.use_output_copy;
     let mut commands = Vec::new();
     if v.is_empty() {
         return commands;
     }
-    let effective_min = if min_copy > 0 { min_copy } else { p };
+    let effective_min = if opts.min_copy > 0 { opts.min_copy } else { p };
 
     // ── Checkpointing parameters (Section 8.1, pp. 347-348) ─────────
     let num_seeds = if r.len() >= p { r.len() - p + 1 } else { 0 };
-    // Auto-size: 2x factor for correcting's |F|=2L convention.
-    let cap = if num_seeds > 0 {
-        next_prime(q.max(2 * num_seeds / p))
-    } else {
-        next_prime(q)
-    }; // |C|
-    let f_size: u64 = if num_seeds > 0 {
-        next_prime(2 * num_seeds) as u64 // |F|
-    } else {
-        1
-    };
-    let m: u64 = if f_size <= cap as u64 {
-        1
-    } else {
-        (f_size + cap as u64 - 1) / cap as u64 // ceil(|F| / |C|)
-    };
     // Biased k (p. 348): pick a V offset, use its footprint mod m.
-    let k: u64 = if v.len() >= p {
-        let fp_k = fingerprint(v, v.len() / 2, p);
-        fp_k % f_size % m
-    } else {
-        0
-    };
+    let k_fp = if v.len() >= p { Some(fingerprint(v, v.len() / 2, p)) } else { None };
+    let cp = checkpoint_params(num_seeds, p, q, opts.max_table, k_fp);
+    let (cap, f_size, m, k) = (cp.cap, cp.f_size, cp.m, cp.k);
 
     if verbose {
         let expected = if m > 0 { num_seeds as u64 / m } else { 0 };
@@ -94,47 +165,35 @@ pub fn diff_correcting(
     }
 
     // Debug counters
-    let mut dbg_build_passed: usize = 0;
-    let mut dbg_build_stored: usize = 0;
-    let mut dbg_build_skipped_collision: usize = 0;
     let mut dbg_scan_checkpoints: usize = 0;
     let mut dbg_scan_match: usize = 0;
     let mut dbg_scan_fp_mismatch: usize = 0;
     let mut dbg_scan_byte_mismatch: usize = 0;
+    let mut dbg_scan_candidates: usize = 0;
+    let mut dbg_scan_out_candidates: usize = 0;
+    let mut dbg_scan_out_match: usize = 0;
 
-    // ── Step (1): Build lookup structure for R (first-found policy) ──
-    let mut h_r_ht: Vec<Option<(u64, usize)>> = if !use_splay { vec![None; cap] } else { Vec::new() };
-    let mut h_r_sp: SplayTree<(u64, usize)> = SplayTree::new(); // (full_fp, offset)
-
-    for a in 0..num_seeds {
-        let fp = fingerprint(r, a, p);
-        let f = fp % f_size;
-        if f % m != k {
-            continue; // not a checkpoint seed
-        }
-        dbg_build_passed += 1;
+    // ── Step (1): Build lookup structure for R (bounded-bucket policy) ──
+    // Each slot keeps up to `bucket_k` (full_fp, offset) pairs, earliest
+    // first; a slot that's already full discards later arrivals so the
+    // result stays deterministic regardless of how the seeds are visited.
+    let bucket_k = opts.bucket_k.max(1);
+    let RTable {
+        h_r_ht,
+        mut h_r_sp,
+        passed: dbg_build_passed,
+        stored: dbg_build_stored,
+        skipped_collision: dbg_build_skipped_collision,
+    } = build_r_table(r, p, &cp, bucket_k, use_splay);
 
-        if use_splay {
-            // insert_or_get implements first-found policy
-            let val = h_r_sp.insert_or_get(fp, (fp, a));
-            if val.1 == a {
-                dbg_build_stored += 1;
-            } else {
-                dbg_build_skipped_collision += 1;
-            }
-        } else {
-            let i = (f / m) as usize;
-            if i >= cap {
-                continue; // safety
-            }
-            if h_r_ht[i].is_none() {
-                h_r_ht[i] = Some((fp, a)); // first-found (Section 7 Step 1)
-                dbg_build_stored += 1;
-            } else {
-                dbg_build_skipped_collision += 1;
-            }
-        }
-    }
+    // `--output-copy`: a second checkpoint table, same cap/f_size/m/k and
+    // same bucket_k bound as R's, but indexing seeds from the reconstructed
+    // output (V-so-far) instead of R. Unlike R's table (built in one pass
+    // up front), this one fills incrementally during the scan below, since
+    // "so-far" only grows as v_c advances.
+    let mut h_v_ht: Vec<Vec<(u64, usize)>> =
+        if use_output_copy && !use_splay { vec![Vec::new(); cap] } else { Vec::new() };
+    let mut h_v_sp: SplayTree<Vec<(u64, usize)>> = SplayTree::new(); // key: full_fp
 
     if verbose {
         let passed_pct = if num_seeds > 0 {
@@ -158,14 +217,26 @@ pub fn diff_correcting(
         );
     }
 
-    // Lookup helper
-    let lookup_r = |h_r_ht: &[Option<(u64, usize)>], h_r_sp: &mut SplayTree<(u64, usize)>, fp_v: u64, f_v: u64| -> Option<(u64, usize)> {
+    // Lookup helper: returns the bucket's candidates (copied out, bounded by
+    // `bucket_k`), for the caller to verify and extend.
+    let lookup_r = |h_r_ht: &[Vec<(u64, usize)>], h_r_sp: &mut SplayTree<Vec<(u64, usize)>>, fp_v: u64, f_v: u64| -> Vec<(u64, usize)> {
+        if use_splay {
+            h_r_sp.find(fp_v).cloned().unwrap_or_default()
+        } else {
+            let i = (f_v / m) as usize;
+            if i >= cap { return Vec::new(); }
+            h_r_ht[i].clone()
+        }
+    };
+
+    // Same lookup shape as `lookup_r`, against the output-so-far table.
+    let lookup_out = |h_v_ht: &[Vec<(u64, usize)>], h_v_sp: &mut SplayTree<Vec<(u64, usize)>>, fp_v: u64, f_v: u64| -> Vec<(u64, usize)> {
         if use_splay {
-            h_r_sp.find(fp_v).copied()
+            h_v_sp.find(fp_v).cloned().unwrap_or_default()
         } else {
             let i = (f_v / m) as usize;
-            if i >= cap { return None; }
-            h_r_ht[i]
+            if i >= cap { return Vec::new(); }
+            h_v_ht[i].clone()
         }
     };
 
@@ -201,49 +272,113 @@ pub fn diff_correcting(
         // Checkpoint passed — look up R.
         dbg_scan_checkpoints += 1;
 
-        let entry = lookup_r(&h_r_ht, &mut h_r_sp, fp_v, f_v);
+        let bucket = lookup_r(&h_r_ht, &mut h_r_sp, fp_v, f_v);
+        dbg_scan_candidates += bucket.iter().filter(|(stored_fp, _)| *stored_fp == fp_v).count();
 
-        let r_offset = match entry {
-            Some((stored_fp, offset)) if stored_fp == fp_v => {
-                // Full fingerprint matches — verify bytes.
-                if r[offset..offset + p] != v[v_c..v_c + p] {
+        // ── Step (5): verify and extend every same-fingerprint candidate
+        // in the bucket, both forwards and backwards (Section 7, Step 5;
+        // Section 8.2 backward extension, p. 349), and keep the one with
+        // the greatest total length. `--output-copy` candidates (sourced
+        // from V itself rather than R) compete on equal footing; the
+        // source offset for those is always < v_c (see the indexing note
+        // below), so the `src < dst` invariant `Command::CopyOut` requires
+        // holds automatically.
+        let mut best: Option<(usize, usize, usize, bool)> = None; // (v_m, src_m, ml, is_out)
+        let mut any_fp_match = false;
+
+        for (stored_fp, offset) in &bucket {
+            if *stored_fp != fp_v {
+                continue;
+            }
+            any_fp_match = true;
+            if r[*offset..*offset + p] != v[v_c..v_c + p] {
+                dbg_scan_byte_mismatch += 1;
+                continue;
+            }
+
+            let fwd = p + extend_forward_chunked(v, v_c + p, r, offset + p);
+            let bwd = extend_backward_chunked(v, v_c, r, *offset);
+
+            let v_m = v_c - bwd;
+            let r_m = offset - bwd;
+            let ml = bwd + fwd;
+
+            if best.map(|(_, _, best_ml, _)| ml > best_ml).unwrap_or(true) {
+                best = Some((v_m, r_m, ml, false));
+            }
+        }
+
+        if use_output_copy {
+            let out_bucket = lookup_out(&h_v_ht, &mut h_v_sp, fp_v, f_v);
+            dbg_scan_out_candidates += out_bucket.iter().filter(|(stored_fp, _)| *stored_fp == fp_v).count();
+
+            for (stored_fp, a) in &out_bucket {
+                if *stored_fp != fp_v {
+                    continue;
+                }
+                any_fp_match = true;
+                if v[*a..*a + p] != v[v_c..v_c + p] {
                     dbg_scan_byte_mismatch += 1;
-                    v_c += 1;
                     continue;
                 }
-                dbg_scan_match += 1;
-                offset
+
+                // Forward extension is allowed to run past v_c itself (the
+                // source is the not-yet-written destination region), which
+                // is what lets a single CopyOut expand a repeating pattern.
+                let fwd = p + extend_forward_chunked(v, v_c + p, v, a + p);
+                let bwd = extend_backward_chunked(v, v_c, v, *a);
+
+                let v_m = v_c - bwd;
+                let a_m = a - bwd;
+                let ml = bwd + fwd;
+
+                if best.map(|(_, _, best_ml, _)| ml > best_ml).unwrap_or(true) {
+                    best = Some((v_m, a_m, ml, true));
+                }
             }
-            Some(_) => {
-                dbg_scan_fp_mismatch += 1;
-                v_c += 1;
-                continue;
+        }
+
+        // Index v_c itself into the output table for later positions to
+        // match against, regardless of what happens to it below — its
+        // bytes are already fixed in V whether this checkpoint ends up
+        // inside a Copy/CopyOut or an Add. Only positions strictly before
+        // the one being looked up above can ever land here, so an
+        // `--output-copy` candidate's source offset is always < v_c.
+        if use_output_copy {
+            if use_splay {
+                let b = h_v_sp.insert_or_get(fp_v, Vec::new());
+                if b.len() < bucket_k {
+                    b.push((fp_v, v_c));
+                }
+            } else {
+                let i = (f_v / m) as usize;
+                if i < cap {
+                    let b = &mut h_v_ht[i];
+                    if b.len() < bucket_k {
+                        b.push((fp_v, v_c));
+                    }
+                }
             }
+        }
+
+        if !any_fp_match {
+            dbg_scan_fp_mismatch += 1;
+            v_c += 1;
+            continue;
+        }
+
+        let (v_m, src_m, ml, is_out) = match best {
+            Some(b) => b,
             None => {
+                // Every candidate shared `fp_v` but failed byte verification.
                 v_c += 1;
                 continue;
             }
         };
-
-        // ── Step (5): extend match forwards and backwards ────────
-        // (Section 7, Step 5; Section 8.2 backward extension, p. 349)
-        let mut fwd = p;
-        while v_c + fwd < v.len() && r_offset + fwd < r.len() && v[v_c + fwd] == r[r_offset + fwd]
-        {
-            fwd += 1;
-        }
-
-        let mut bwd: usize = 0;
-        while v_c >= bwd + 1
-            && r_offset >= bwd + 1
-            && v[v_c - bwd - 1] == r[r_offset - bwd - 1]
-        {
-            bwd += 1;
+        dbg_scan_match += 1;
+        if is_out {
+            dbg_scan_out_match += 1;
         }
-
-        let v_m = v_c - bwd;
-        let r_m = r_offset - bwd;
-        let ml = bwd + fwd;
         let match_end = v_m + ml;
 
         // Filter: skip matches shorter than --min-copy
@@ -252,6 +387,14 @@ pub fn diff_correcting(
             continue;
         }
 
+        let make_cmd = |offset: usize, length: usize| {
+            if is_out {
+                Command::CopyOut { offset, length }
+            } else {
+                Command::Copy { offset, length }
+            }
+        };
+
         // ── Step (6): encode with correction ─────────────────────
         if v_s <= v_m {
             // (6a) match is entirely in unencoded suffix (Section 7)
@@ -280,10 +423,7 @@ pub fn diff_correcting(
             buf.push_back(BufEntry {
                 v_start: v_m,
                 v_end: match_end,
-                cmd: Command::Copy {
-                    offset: r_m,
-                    length: ml,
-                },
+                cmd: make_cmd(src_m, ml),
                 dummy: false,
             });
             v_s = match_end;
@@ -340,10 +480,7 @@ pub fn diff_correcting(
                 buf.push_back(BufEntry {
                     v_start: effective_start,
                     v_end: match_end,
-                    cmd: Command::Copy {
-                        offset: r_m + adj,
-                        length: new_len,
-                    },
+                    cmd: make_cmd(src_m + adj, new_len),
                     dummy: false,
                 });
             }
@@ -367,6 +504,7 @@ pub fn diff_correcting(
         let mut total_copy: usize = 0;
         let mut total_add: usize = 0;
         let mut num_copies: usize = 0;
+        let mut num_copy_outs: usize = 0;
         let mut num_adds: usize = 0;
         for cmd in &commands {
             match cmd {
@@ -375,10 +513,19 @@ pub fn diff_correcting(
                     num_copies += 1;
                     copy_lens.push(*length);
                 }
+                Command::CopyOut { length, .. } => {
+                    total_copy += length;
+                    num_copy_outs += 1;
+                    copy_lens.push(*length);
+                }
                 Command::Add { data } => {
                     total_add += data.len();
                     num_adds += 1;
                 }
+                Command::Run { length, .. } => {
+                    total_add += length;
+                    num_adds += 1;
+                }
             }
         }
         let v_seeds = if v.len() >= p { v.len() - p + 1 } else { 0 };
@@ -398,17 +545,30 @@ pub fn diff_correcting(
         } else {
             0.0
         };
+        let avg_candidates = if dbg_scan_checkpoints > 0 {
+            dbg_scan_candidates as f64 / dbg_scan_checkpoints as f64
+        } else {
+            0.0
+        };
         eprintln!(
             "  scan: {} V positions, {} checkpoints ({:.3}%), {} matches\n  \
              scan: hit rate {:.1}% (of checkpoints), \
-             fp collisions {}, byte mismatches {}",
+             fp collisions {}, byte mismatches {}\n  \
+             scan: {:.2} same-fingerprint candidates examined per checkpoint (bucket_k={})",
             v_seeds, dbg_scan_checkpoints, cp_pct, dbg_scan_match,
-            hit_pct, dbg_scan_fp_mismatch, dbg_scan_byte_mismatch
+            hit_pct, dbg_scan_fp_mismatch, dbg_scan_byte_mismatch,
+            avg_candidates, bucket_k
         );
+        if use_output_copy {
+            eprintln!(
+                "  scan: {} output-table candidates examined, {} matches won by the output table",
+                dbg_scan_out_candidates, dbg_scan_out_match
+            );
+        }
         eprintln!(
-            "  result: {} copies ({} bytes), {} adds ({} bytes)\n  \
+            "  result: {} copies ({} bytes), {} copy-outs, {} adds ({} bytes)\n  \
              result: copy coverage {:.1}%, output {} bytes",
-            num_copies, total_copy, num_adds, total_add, copy_pct, total_out
+            num_copies, total_copy, num_copy_outs, num_adds, total_add, copy_pct, total_out
         );
         if !copy_lens.is_empty() {
             copy_lens.sort();
@@ -425,10 +585,477 @@ pub fn diff_correcting(
         }
     }
 
-    commands
+    let commands = refine_gaps(r, commands, opts, effective_min);
+
+    super::runify(commands)
+}
+
+/// Post-pass (Section 8.1's checkpoint spacing `m ≈ p` necessarily misses
+/// matches shorter than `p` that fall entirely between two checkpoints):
+/// re-run the correcting matcher on every `Add` gap left by the main scan,
+/// at half the seed length, splicing any discovered copies back in. Each
+/// further `refine_depth` level halves the seed length again and only
+/// re-scans the `Add`s still left over from the previous level, so the
+/// cost is bounded by how much of V the earlier levels failed to cover.
+/// Stops early once halving would take the seed length below 2.
+fn refine_gaps(r: &[u8], commands: Vec<Command>, opts: &DiffOptions, effective_min: usize) -> Vec<Command> {
+    if !opts.refine_gaps {
+        return commands;
+    }
+
+    let mut pending = commands;
+    let mut p_level = opts.p;
+    for level in 1..=opts.refine_depth {
+        let next_p = (p_level / 2).max(2);
+        if next_p >= p_level {
+            break; // can't halve the seed length any further
+        }
+        p_level = next_p;
+        let sub_opts = DiffOptions {
+            p: p_level,
+            min_copy: effective_min,
+            refine_gaps: false,
+            ..opts.clone()
+        };
+
+        let mut next = Vec::with_capacity(pending.len());
+        let mut reclaimed: usize = 0;
+        // Tracks the global output offset the next command will start
+        // writing at, i.e. what `place_commands` would eventually assign it
+        // as `dst` — needed below to rebase a spliced gap's `CopyOut`s.
+        let mut dst: usize = 0;
+        for cmd in pending {
+            match cmd {
+                Command::Add { data }
+                    if data.len() >= opts.refine_min_gap && data.len() >= p_level =>
+                {
+                    let gap_start = dst;
+                    dst += data.len();
+                    let mut spliced = diff_correcting(r, &data, &sub_opts);
+                    // `diff_correcting(r, &data, ..)` computes `CopyOut`
+                    // offsets relative to `data`'s own local output (indices
+                    // starting at 0), but `CopyOut.offset` is an absolute
+                    // position in the *whole* reconstructed output
+                    // everywhere else (`apply::copy_out_forward`). Rebase by
+                    // this gap's starting position or the spliced commands
+                    // silently reconstruct the wrong bytes once spliced
+                    // somewhere other than output offset 0.
+                    for c in &mut spliced {
+                        if let Command::CopyOut { offset, .. } = c {
+                            *offset += gap_start;
+                        }
+                    }
+                    reclaimed += spliced
+                        .iter()
+                        .map(|c| match c {
+                            Command::Copy { length, .. } | Command::CopyOut { length, .. } => *length,
+                            _ => 0,
+                        })
+                        .sum::<usize>();
+                    next.extend(spliced);
+                }
+                other => {
+                    dst += match &other {
+                        Command::Copy { length, .. }
+                        | Command::CopyOut { length, .. }
+                        | Command::Run { length, .. } => *length,
+                        Command::Add { data } => data.len(),
+                    };
+                    next.push(other);
+                }
+            }
+        }
+        pending = next;
+
+        if opts.verbose && reclaimed > 0 {
+            eprintln!(
+                "  refine: level {} (p'={}) reclaimed {} bytes",
+                level, p_level, reclaimed
+            );
+        }
+    }
+    pending
 }
 
 /// Convenience wrapper with default parameters.
 pub fn diff_correcting_default(r: &[u8], v: &[u8]) -> Vec<Command> {
-    diff_correcting(r, v, SEED_LEN, TABLE_SIZE, 256, false, false, 0)
+    diff_correcting(r, v, &DiffOptions::default())
+}
+
+/// A `window_bytes` suffix of V backed by a `Read`, with `base` tracking the
+/// global offset of the buffer's front byte (same shape as
+/// `streaming::Window`, duplicated locally since that one isn't `pub`).
+struct VWindow<S> {
+    source: S,
+    buf: VecDeque<u8>,
+    base: usize,
+    eof: bool,
+}
+
+impl<S: Read> VWindow<S> {
+    fn new(source: S) -> Self {
+        VWindow { source, buf: VecDeque::new(), base: 0, eof: false }
+    }
+
+    fn ensure(&mut self, want: usize) {
+        let mut chunk = [0u8; 64 * 1024];
+        while self.buf.len() < want && !self.eof {
+            match self.source.read(&mut chunk) {
+                Ok(0) | Err(_) => self.eof = true,
+                Ok(n) => self.buf.extend(chunk[..n].iter().copied()),
+            }
+        }
+    }
+
+    fn buffered_end(&self) -> usize {
+        self.base + self.buf.len()
+    }
+
+    fn evict_before(&mut self, keep_from: usize) {
+        if keep_from > self.base {
+            let drop_n = (keep_from - self.base).min(self.buf.len());
+            self.buf.drain(..drop_n);
+            self.base += drop_n;
+        }
+    }
+
+    #[inline]
+    fn at(&self, pos: usize) -> u8 {
+        self.buf[pos - self.base]
+    }
+
+    fn has(&self, pos: usize) -> bool {
+        pos >= self.base && pos < self.buffered_end()
+    }
+
+    /// Fingerprint of the `p` bytes starting at `pos`, or `None` if they
+    /// aren't (and can no longer become) fully buffered.
+    fn seed(&mut self, pos: usize, p: usize) -> Option<u64> {
+        self.ensure(pos - self.base + p);
+        if pos + p > self.buffered_end() {
+            return None;
+        }
+        let mut tmp = Vec::with_capacity(p);
+        for i in 0..p {
+            tmp.push(self.at(pos + i));
+        }
+        Some(fingerprint(&tmp, 0, p))
+    }
+}
+
+/// Commit the unmatched `[v_s, v_c)` gap as an `Add` once it grows as large
+/// as `window_bytes`, so the window can evict those bytes and stay bounded
+/// even across a long stretch with no checkpoint hits (mirrors
+/// `streaming::diff_streaming`'s own periodic-flush guard).
+fn flush_gap_if_needed<Vr: Read>(
+    v_win: &mut VWindow<Vr>,
+    buf: &mut VecDeque<BufEntry>,
+    buf_cap: usize,
+    window_bytes: usize,
+    v_s: &mut usize,
+    v_c: usize,
+    emit: &mut dyn FnMut(Command),
+) {
+    if v_c - *v_s < window_bytes {
+        return;
+    }
+    if buf.len() >= buf_cap {
+        let oldest = buf.pop_front().unwrap();
+        if !oldest.dummy {
+            emit(oldest.cmd);
+        }
+    }
+    v_win.ensure(v_c - v_win.base);
+    let data: Vec<u8> = (*v_s..v_c).map(|i| v_win.at(i)).collect();
+    buf.push_back(BufEntry { v_start: *v_s, v_end: v_c, cmd: Command::Add { data }, dummy: false });
+    *v_s = v_c;
+}
+
+/// Streaming companion to `diff_correcting` for a V too large to hold in
+/// memory (Section 7/8). `r` is still an ordinary slice — R's checkpoint
+/// table is the only structure the matcher needs random access to, and it
+/// stays a fixed, bounded size regardless of `|R|` (Section 8.1) — but
+/// `version` is consumed incrementally from a `Read` through a bounded
+/// `window_bytes` lookback window, and commands are emitted through `sink`
+/// as they leave the tail-correction buffer instead of being collected into
+/// a `Vec`.
+///
+/// Three differences from `diff_correcting` given the same `r`/`opts`:
+/// - `k` (the checkpoint-class bias, p. 348) normally comes from a V seed
+///   near its midpoint; since V's length isn't known up front here, this
+///   path always uses `k = 0` instead.
+/// - `use_output_copy` isn't supported (self-referential copies would need
+///   an output-side table windowed the same way V is) and is ignored.
+/// - `refine_gaps` isn't applied here either: the gap-refinement post-pass
+///   re-scans each whole `Add` against R, which assumes the commands are
+///   already fully collected rather than streamed out through `sink`.
+///
+/// Backward extension and tail correction (Section 5.1) only reach as far
+/// back into V as `window_bytes` allows; a match that would need to look
+/// further back than that is extended only up to the window boundary, the
+/// same trade-off `streaming::diff_streaming` makes for its own window.
+/// Choose `window_bytes` comfortably larger than the longest backward
+/// extension plus `opts.buf_cap` pending entries you expect to see.
+pub fn diff_correcting_stream<Vr: Read>(
+    r: &[u8],
+    version: Vr,
+    opts: &DiffOptions,
+    window_bytes: usize,
+    mut sink: impl FnMut(Command),
+) {
+    let p = opts.p.max(1);
+    let buf_cap = opts.buf_cap;
+    let verbose = opts.verbose;
+    let use_splay = opts.use_splay;
+    let effective_min = if opts.min_copy > 0 { opts.min_copy } else { p };
+    let window_bytes = window_bytes.max(p * 4 + buf_cap);
+
+    let num_seeds = if r.len() >= p { r.len() - p + 1 } else { 0 };
+    let cp = checkpoint_params(num_seeds, p, opts.q, opts.max_table, None);
+    let bucket_k = opts.bucket_k.max(1);
+    let RTable {
+        h_r_ht,
+        mut h_r_sp,
+        passed: dbg_build_passed,
+        stored: dbg_build_stored,
+        skipped_collision: dbg_build_skipped_collision,
+    } = build_r_table(r, p, &cp, bucket_k, use_splay);
+
+    if verbose {
+        eprintln!(
+            "correcting (stream): {}, |C|={} |F|={} m={} k={} (k forced to 0: V length unknown)\n  \
+             build: {} seeds, {} passed checkpoint, {} stored, {} collisions",
+            if use_splay { "splay tree" } else { "hash table" },
+            cp.cap, cp.f_size, cp.m, cp.k,
+            num_seeds, dbg_build_passed, dbg_build_stored, dbg_build_skipped_collision
+        );
+    }
+
+    let lookup_r = |h_r_ht: &[Vec<(u64, usize)>], h_r_sp: &mut SplayTree<Vec<(u64, usize)>>, fp_v: u64, f_v: u64| -> Vec<(u64, usize)> {
+        if use_splay {
+            h_r_sp.find(fp_v).cloned().unwrap_or_default()
+        } else {
+            let i = (f_v / cp.m) as usize;
+            if i >= cp.cap { return Vec::new(); }
+            h_r_ht[i].clone()
+        }
+    };
+
+    let mut v_win = VWindow::new(version);
+    let mut buf: VecDeque<BufEntry> = VecDeque::new();
+    let mut v_c: usize = 0;
+    let mut v_s: usize = 0;
+
+    let mut dbg_scan_checkpoints: usize = 0;
+    let mut dbg_scan_match: usize = 0;
+    let mut total_copy: usize = 0;
+    let mut total_add: usize = 0;
+    let mut num_copies: usize = 0;
+    let mut num_adds: usize = 0;
+
+    // Wraps `sink` so every command leaving the buffer is tallied for the
+    // verbose summary at the end, the same totals `diff_correcting` reports
+    // from its own finished `Vec<Command>`.
+    // Each Add is split into Add/Run segments first, as `diff_correcting`
+    // does via `runify` on its finished output.
+    let mut emit = |cmd: Command| {
+        for cmd in super::runify(vec![cmd]) {
+            match &cmd {
+                Command::Copy { length, .. } => {
+                    total_copy += length;
+                    num_copies += 1;
+                }
+                Command::Add { data } => {
+                    total_add += data.len();
+                    num_adds += 1;
+                }
+                Command::CopyOut { length, .. } => {
+                    total_copy += length;
+                    num_copies += 1;
+                }
+                Command::Run { length, .. } => {
+                    total_add += length;
+                    num_adds += 1;
+                }
+            }
+            sink(cmd);
+        }
+    };
+
+    let flush_front = |buf: &mut VecDeque<BufEntry>, emit: &mut dyn FnMut(Command)| {
+        let oldest = buf.pop_front().unwrap();
+        if !oldest.dummy {
+            emit(oldest.cmd);
+        }
+    };
+
+    loop {
+        v_win.ensure(v_c - v_win.base + p);
+        if !v_win.has(v_c) || v_c + p > v_win.buffered_end() {
+            break; // fewer than p bytes remain — no further seed possible
+        }
+
+        let fp_v = v_win.seed(v_c, p).unwrap();
+        let f_v = fp_v % cp.f_size;
+        if f_v % cp.m != cp.k {
+            v_c += 1;
+            flush_gap_if_needed(&mut v_win, &mut buf, buf_cap, window_bytes, &mut v_s, v_c, &mut emit);
+            v_win.evict_before(v_c.saturating_sub(window_bytes));
+            continue;
+        }
+        dbg_scan_checkpoints += 1;
+
+        let bucket = lookup_r(&h_r_ht, &mut h_r_sp, fp_v, f_v);
+        let mut best: Option<(usize, usize, usize)> = None; // (v_m, r_m, ml)
+
+        for (stored_fp, offset) in &bucket {
+            if *stored_fp != fp_v {
+                continue;
+            }
+            if !(0..p).all(|i| v_win.at(v_c + i) == r[*offset + i]) {
+                continue;
+            }
+
+            let mut fwd = p;
+            while v_win.has(v_c + fwd) || {
+                v_win.ensure(v_c + fwd - v_win.base + 1);
+                v_win.has(v_c + fwd)
+            } {
+                if offset + fwd >= r.len() || v_win.at(v_c + fwd) != r[offset + fwd] {
+                    break;
+                }
+                fwd += 1;
+            }
+
+            let mut bwd: usize = 0;
+            while v_c > bwd && *offset > bwd && v_win.has(v_c - bwd - 1) && v_win.at(v_c - bwd - 1) == r[offset - bwd - 1] {
+                bwd += 1;
+            }
+
+            let v_m = v_c - bwd;
+            let r_m = offset - bwd;
+            let ml = bwd + fwd;
+            if best.map(|(_, _, best_ml)| ml > best_ml).unwrap_or(true) {
+                best = Some((v_m, r_m, ml));
+            }
+        }
+
+        let Some((v_m, r_m, ml)) = best else {
+            v_c += 1;
+            flush_gap_if_needed(&mut v_win, &mut buf, buf_cap, window_bytes, &mut v_s, v_c, &mut emit);
+            v_win.evict_before(v_c.saturating_sub(window_bytes));
+            continue;
+        };
+        if ml < effective_min {
+            v_c += 1;
+            flush_gap_if_needed(&mut v_win, &mut buf, buf_cap, window_bytes, &mut v_s, v_c, &mut emit);
+            v_win.evict_before(v_c.saturating_sub(window_bytes));
+            continue;
+        }
+        dbg_scan_match += 1;
+        let match_end = v_m + ml;
+
+        let read_range = |v_win: &mut VWindow<Vr>, from: usize, to: usize| -> Vec<u8> {
+            v_win.ensure(to - v_win.base);
+            (from..to).map(|i| v_win.at(i)).collect()
+        };
+
+        if v_s <= v_m {
+            if v_s < v_m {
+                if buf.len() >= buf_cap {
+                    flush_front(&mut buf, &mut emit);
+                }
+                buf.push_back(BufEntry {
+                    v_start: v_s,
+                    v_end: v_m,
+                    cmd: Command::Add { data: read_range(&mut v_win, v_s, v_m) },
+                    dummy: false,
+                });
+            }
+            if buf.len() >= buf_cap {
+                flush_front(&mut buf, &mut emit);
+            }
+            buf.push_back(BufEntry {
+                v_start: v_m,
+                v_end: match_end,
+                cmd: Command::Copy { offset: r_m, length: ml },
+                dummy: false,
+            });
+            v_s = match_end;
+        } else {
+            // Tail correction (Section 5.1): same logic as `diff_correcting`,
+            // but a partial-Add trim falls back to leaving the entry alone
+            // if the window has already evicted the bytes it would need.
+            let mut effective_start = v_s;
+            while let Some(tail) = buf.back() {
+                if tail.dummy {
+                    buf.pop_back();
+                    continue;
+                }
+                if tail.v_start >= v_m && tail.v_end <= match_end {
+                    effective_start = effective_start.min(tail.v_start);
+                    buf.pop_back();
+                    continue;
+                }
+                if tail.v_end > v_m && tail.v_start < v_m {
+                    if matches!(tail.cmd, Command::Add { .. }) && v_win.has(tail.v_start) {
+                        let back = buf.back_mut().unwrap();
+                        back.cmd = Command::Add { data: read_range(&mut v_win, back.v_start, v_m) };
+                        back.v_end = v_m;
+                        effective_start = effective_start.min(v_m);
+                    }
+                    break;
+                }
+                break;
+            }
+
+            let adj = effective_start - v_m;
+            let new_len = match_end - effective_start;
+            if new_len > 0 {
+                if buf.len() >= buf_cap {
+                    flush_front(&mut buf, &mut emit);
+                }
+                buf.push_back(BufEntry {
+                    v_start: effective_start,
+                    v_end: match_end,
+                    cmd: Command::Copy { offset: r_m + adj, length: new_len },
+                    dummy: false,
+                });
+            }
+            v_s = match_end;
+        }
+
+        v_c = match_end;
+        v_win.evict_before(v_c.saturating_sub(window_bytes));
+    }
+
+    flush_buf_to_sink(&mut buf, &mut emit);
+    let mut tail = Vec::new();
+    loop {
+        v_win.ensure(v_s - v_win.base + 1);
+        if !v_win.has(v_s) {
+            break;
+        }
+        tail.push(v_win.at(v_s));
+        v_s += 1;
+    }
+    if !tail.is_empty() {
+        emit(Command::Add { data: tail });
+    }
+
+    if verbose {
+        eprintln!(
+            "  scan: {} checkpoints, {} matches\n  \
+             result: {} copies ({} bytes), {} adds ({} bytes)",
+            dbg_scan_checkpoints, dbg_scan_match, num_copies, total_copy, num_adds, total_add
+        );
+    }
+}
+
+fn flush_buf_to_sink(buf: &mut VecDeque<BufEntry>, sink: &mut dyn FnMut(Command)) {
+    for entry in buf.drain(..) {
+        if !entry.dummy {
+            sink(entry.cmd);
+        }
+    }
 }
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
+use crate::block_index::BlockIndex;
 use crate::hash::{fingerprint, RollingHash};
 use crate::splay::SplayTree;
-use crate::types::{Command, SEED_LEN, TABLE_SIZE};
+use crate::types::{Command, DiffOptions};
 
 /// Greedy algorithm (Section 3.1, Figure 2).
 ///
@@ -11,21 +12,33 @@ use crate::types::{Command, SEED_LEN, TABLE_SIZE};
 /// Uses a chained hash table (HashMap) or splay tree storing ALL offsets
 /// in R per fingerprint.
 /// Time: O(|V| * |R|) worst case. Space: O(|R|).
-pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_splay: bool, min_copy: usize) -> Vec<Command> {
+pub fn diff_greedy(r: &[u8], v: &[u8], opts: &DiffOptions) -> Vec<Command> {
+    let verbose = opts.verbose;
+    let use_splay = opts.use_splay;
+    let use_block_index = opts.use_block_index;
+    let use_output_copy = opts.use_output_copy;
     let mut commands = Vec::new();
     if v.is_empty() {
         return commands;
     }
     // --min-copy raises the seed length so we never fingerprint at a
     // granularity finer than the minimum copy threshold.
-    let p = if min_copy > 0 { p.max(min_copy) } else { p };
+    let p = if opts.min_copy > 0 { opts.p.max(opts.min_copy) } else { opts.p };
 
     // Step (1): Build lookup structure for R keyed by full fingerprint.
-    // Hash table (default) or splay tree (--splay).
+    // Hash table (default), splay tree (--splay), or block index
+    // (--block-index) — a faster keyed non-cryptographic hash in place of
+    // the Karp-Rabin rolling fingerprint, useful when nothing downstream
+    // needs the rolling-update property.
     let mut h_r: HashMap<u64, Vec<usize>> = HashMap::new();
     let mut splay_r: SplayTree<Vec<usize>> = SplayTree::new();
+    let block_r: Option<BlockIndex> = if use_block_index {
+        Some(BlockIndex::build(r, p))
+    } else {
+        None
+    };
 
-    if r.len() >= p {
+    if !use_block_index && r.len() >= p {
         let mut rh = RollingHash::new(r, 0, p);
         if use_splay {
             splay_r.insert_or_get(rh.value(), Vec::new()).push(0);
@@ -45,7 +58,7 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
     if verbose {
         eprintln!(
             "greedy: {}, |R|={}, |V|={}, seed_len={}",
-            if use_splay { "splay tree" } else { "hash table" },
+            if use_block_index { "block index" } else if use_splay { "splay tree" } else { "hash table" },
             r.len(), v.len(), p
         );
     }
@@ -58,6 +71,16 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
     let mut rh_v: Option<RollingHash> = if v.len() >= p { Some(RollingHash::new(v, 0, p)) } else { None };
     let mut rh_v_pos: usize = 0;
 
+    // --output-copy: index the already-emitted prefix of V itself (the
+    // reconstructed-output region, `[0, v_c)`), reusing the same
+    // Karp-Rabin rolling hash as the R index above. `out_indexed_upto`
+    // tracks how many seed start positions `a` (with `a + p <= v_c`) have
+    // been inserted so far; it only ever grows, so positions are indexed
+    // exactly once regardless of how far `v_c` jumps after a match.
+    let mut h_v: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut rh_out: Option<RollingHash> = None;
+    let mut out_indexed_upto: usize = 0;
+
     loop {
         // Step (3)
         if v_c + p > v.len() {
@@ -81,11 +104,33 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
             break;
         };
 
+        // Bring the output index up to date with `v_c` before searching it
+        // (see the `h_v`/`out_indexed_upto` comment above).
+        if use_output_copy && v_c >= p {
+            let target = v_c - p + 1;
+            while out_indexed_upto < target {
+                let a = out_indexed_upto;
+                let fp_out = if let Some(ref mut rh) = rh_out {
+                    rh.roll(v[a - 1], v[a + p - 1]);
+                    rh.value()
+                } else {
+                    let rh_new = RollingHash::new(v, a, p);
+                    let val = rh_new.value();
+                    rh_out = Some(rh_new);
+                    val
+                };
+                h_v.entry(fp_out).or_default().push(a);
+                out_indexed_upto += 1;
+            }
+        }
+
         // Steps (4)+(5): find the longest matching substring
         let mut best_rm: Option<usize> = None;
         let mut best_len: usize = 0;
 
-        let offsets: Option<&[usize]> = if use_splay {
+        let offsets: Option<&[usize]> = if let Some(ref idx) = block_r {
+            Some(idx.query(v, v_c))
+        } else if use_splay {
             splay_r.find(fp_v).map(|v| v.as_slice())
         } else {
             h_r.get(&fp_v).map(|v| v.as_slice())
@@ -109,7 +154,38 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
             }
         }
 
-        if best_len < p {
+        // Self-referential match against the reconstructed-output prefix
+        // (VCDIFF-style target window). The source region is allowed to
+        // overlap the destination (`a < v_c` but `a + ml` may run past
+        // `v_c`), which is what makes run-length-style expansion cheap;
+        // `apply::copy_out_forward` replays such overlap byte-by-byte.
+        let mut best_om: Option<usize> = None;
+        let mut best_out_len: usize = 0;
+
+        if use_output_copy {
+            if let Some(offsets) = h_v.get(&fp_v) {
+                for &a in offsets {
+                    if v[a..a + p] != v[v_c..v_c + p] {
+                        continue;
+                    }
+                    let mut ml = p;
+                    while v_c + ml < v.len() && v[v_c + ml] == v[a + ml] {
+                        ml += 1;
+                    }
+                    if ml > best_out_len {
+                        best_out_len = ml;
+                        best_om = Some(a);
+                    }
+                }
+            }
+        }
+
+        // Prefer the in-output match whenever it is at least as long as
+        // the reference match (ties broken in its favor, since it needs
+        // no reference access at apply time).
+        let use_out = best_out_len >= p && best_out_len >= best_len;
+
+        if !use_out && best_len < p {
             v_c += 1;
             continue;
         }
@@ -120,14 +196,23 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
                 data: v[v_s..v_c].to_vec(),
             });
         }
-        commands.push(Command::Copy {
-            offset: best_rm.unwrap(),
-            length: best_len,
-        });
-        v_s = v_c + best_len;
+        if use_out {
+            commands.push(Command::CopyOut {
+                offset: best_om.unwrap(),
+                length: best_out_len,
+            });
+            v_s = v_c + best_out_len;
+            v_c += best_out_len;
+        } else {
+            commands.push(Command::Copy {
+                offset: best_rm.unwrap(),
+                length: best_len,
+            });
+            v_s = v_c + best_len;
 
-        // Step (7)
-        v_c += best_len;
+            // Step (7)
+            v_c += best_len;
+        }
     }
 
     // Step (8)
@@ -145,7 +230,7 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
         let mut num_adds: usize = 0;
         for cmd in &commands {
             match cmd {
-                Command::Copy { length, .. } => {
+                Command::Copy { length, .. } | Command::CopyOut { length, .. } => {
                     total_copy += length;
                     num_copies += 1;
                     copy_lens.push(*length);
@@ -154,6 +239,10 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
                     total_add += data.len();
                     num_adds += 1;
                 }
+                Command::Run { length, .. } => {
+                    total_add += length;
+                    num_adds += 1;
+                }
             }
         }
         let total_out = total_copy + total_add;
@@ -182,10 +271,10 @@ pub fn diff_greedy(r: &[u8], v: &[u8], p: usize, _q: usize, verbose: bool, use_s
         }
     }
 
-    commands
+    super::runify(commands)
 }
 
 /// Convenience wrapper with default parameters.
 pub fn diff_greedy_default(r: &[u8], v: &[u8]) -> Vec<Command> {
-    diff_greedy(r, v, SEED_LEN, TABLE_SIZE, false, false, 0)
+    diff_greedy(r, v, &DiffOptions::default())
 }
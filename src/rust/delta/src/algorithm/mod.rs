@@ -1,19 +1,68 @@
 pub mod greedy;
 pub mod onepass;
 pub mod correcting;
+pub mod streaming;
+pub mod cdc;
+pub mod signature;
 
-use crate::types::{Algorithm, Command, DiffOptions};
+use crate::types::{Algorithm, Chunking, Command, DiffOptions, DELTA_MIN_RUN_LENGTH};
+
+/// Split each `Add`'s data into `Add`/`Run` segments, replacing any stretch
+/// of `DELTA_MIN_RUN_LENGTH` or more identical bytes with a `Run` — cheaper
+/// to encode than the literal bytes it replaces. Every differencing
+/// algorithm's output passes through here before being returned, so the
+/// `Run` opcode doesn't need to be matching-algorithm-aware.
+pub(crate) fn runify(commands: Vec<Command>) -> Vec<Command> {
+    let mut out = Vec::with_capacity(commands.len());
+    for cmd in commands {
+        match cmd {
+            Command::Add { data } => split_runs(&mut out, data),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn split_runs(out: &mut Vec<Command>, data: Vec<u8>) {
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i < data.len() {
+        let byte = data[i];
+        let mut j = i + 1;
+        while j < data.len() && data[j] == byte {
+            j += 1;
+        }
+        let run_len = j - i;
+        if run_len >= DELTA_MIN_RUN_LENGTH {
+            if literal_start < i {
+                out.push(Command::Add {
+                    data: data[literal_start..i].to_vec(),
+                });
+            }
+            out.push(Command::Run { byte, length: run_len });
+            literal_start = j;
+        }
+        i = j;
+    }
+    if literal_start < data.len() {
+        out.push(Command::Add {
+            data: data[literal_start..].to_vec(),
+        });
+    }
+}
 
 /// Print shared verbose statistics for diff algorithm output.
 pub(crate) fn print_command_stats(commands: &[Command]) {
     let mut copy_lens: Vec<usize> = Vec::new();
     let mut total_copy: usize = 0;
     let mut total_add: usize = 0;
+    let mut total_run: usize = 0;
     let mut num_copies: usize = 0;
     let mut num_adds: usize = 0;
+    let mut num_runs: usize = 0;
     for cmd in commands {
         match cmd {
-            Command::Copy { length, .. } => {
+            Command::Copy { length, .. } | Command::CopyOut { length, .. } => {
                 total_copy += length;
                 num_copies += 1;
                 copy_lens.push(*length);
@@ -22,18 +71,22 @@ pub(crate) fn print_command_stats(commands: &[Command]) {
                 total_add += data.len();
                 num_adds += 1;
             }
+            Command::Run { length, .. } => {
+                total_run += length;
+                num_runs += 1;
+            }
         }
     }
-    let total_out = total_copy + total_add;
+    let total_out = total_copy + total_add + total_run;
     let copy_pct = if total_out > 0 {
         total_copy as f64 / total_out as f64 * 100.0
     } else {
         0.0
     };
     eprintln!(
-        "  result: {} copies ({} bytes), {} adds ({} bytes)\n  \
+        "  result: {} copies ({} bytes), {} adds ({} bytes), {} runs ({} bytes)\n  \
          result: copy coverage {:.1}%, output {} bytes",
-        num_copies, total_copy, num_adds, total_add, copy_pct, total_out
+        num_copies, total_copy, num_adds, total_add, num_runs, total_run, copy_pct, total_out
     );
     if !copy_lens.is_empty() {
         copy_lens.sort();
@@ -50,13 +103,81 @@ pub(crate) fn print_command_stats(commands: &[Command]) {
     }
 }
 
+/// Extend a known match `p` bytes forward by comparing 8 bytes at a time
+/// instead of one, used by `correcting`'s Step 5 where long matches are
+/// common and the scalar byte loop dominates runtime.
+///
+/// `a_pos`/`b_pos` are the positions the match already covers (i.e. the
+/// first unverified byte); returns how many further bytes match, so the
+/// caller adds this to its existing match length. Falls back to a scalar
+/// loop for the final under-8-byte tail.
+#[inline]
+pub(crate) fn extend_forward_chunked(a: &[u8], a_pos: usize, b: &[u8], b_pos: usize) -> usize {
+    let mut fwd = 0;
+    while a_pos + fwd + 8 <= a.len() && b_pos + fwd + 8 <= b.len() {
+        let wa = u64::from_ne_bytes(a[a_pos + fwd..a_pos + fwd + 8].try_into().unwrap());
+        let wb = u64::from_ne_bytes(b[b_pos + fwd..b_pos + fwd + 8].try_into().unwrap());
+        let diff = wa ^ wb;
+        if diff != 0 {
+            #[cfg(target_endian = "little")]
+            let byte = diff.trailing_zeros() / 8;
+            #[cfg(target_endian = "big")]
+            let byte = diff.leading_zeros() / 8;
+            return fwd + byte as usize;
+        }
+        fwd += 8;
+    }
+    while a_pos + fwd < a.len() && b_pos + fwd < b.len() && a[a_pos + fwd] == b[b_pos + fwd] {
+        fwd += 1;
+    }
+    fwd
+}
+
+/// Mirror of `extend_forward_chunked` for backward extension: `a_pos`/`b_pos`
+/// are the positions the match already starts at (i.e. the first byte
+/// *before* which nothing has been verified yet); returns how many further
+/// bytes — walking toward index 0 — match.
+#[inline]
+pub(crate) fn extend_backward_chunked(a: &[u8], a_pos: usize, b: &[u8], b_pos: usize) -> usize {
+    let mut bwd = 0;
+    while a_pos >= bwd + 8 && b_pos >= bwd + 8 {
+        let wa = u64::from_ne_bytes(a[a_pos - bwd - 8..a_pos - bwd].try_into().unwrap());
+        let wb = u64::from_ne_bytes(b[b_pos - bwd - 8..b_pos - bwd].try_into().unwrap());
+        let diff = wa ^ wb;
+        if diff != 0 {
+            // The byte closest to a_pos/b_pos is the chunk's most
+            // significant byte on little-endian (it was read last into
+            // the u64), so the first backward mismatch is the highest
+            // nonzero byte rather than the lowest.
+            #[cfg(target_endian = "little")]
+            let byte = diff.leading_zeros() / 8;
+            #[cfg(target_endian = "big")]
+            let byte = diff.trailing_zeros() / 8;
+            return bwd + byte as usize;
+        }
+        bwd += 8;
+    }
+    while a_pos > bwd && b_pos > bwd && a[a_pos - bwd - 1] == b[b_pos - bwd - 1] {
+        bwd += 1;
+    }
+    bwd
+}
+
 /// Dispatch to the appropriate differencing algorithm.
+///
+/// `opts.chunking` is a cross-cutting override: when it selects
+/// `Chunking::Rabin`, matching always goes through `cdc::diff_cdc`
+/// regardless of `algorithm`, since content-defined chunking replaces the
+/// seed-grid scan that each `Algorithm` variant otherwise performs.
 pub fn diff(
     algorithm: Algorithm,
     r: &[u8],
     v: &[u8],
     opts: &DiffOptions,
 ) -> Vec<Command> {
+    if matches!(opts.chunking, Chunking::Rabin { .. }) {
+        return cdc::diff_cdc(r, v, opts);
+    }
     match algorithm {
         Algorithm::Greedy => greedy::diff_greedy(r, v, opts),
         Algorithm::Onepass => onepass::diff_onepass(r, v, opts),
@@ -68,3 +189,108 @@ pub fn diff(
 pub fn diff_default(algorithm: Algorithm, r: &[u8], v: &[u8]) -> Vec<Command> {
     diff(algorithm, r, v, &DiffOptions::default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scalar byte-by-byte reference implementation, mirroring the loops
+    /// `extend_forward_chunked`/`extend_backward_chunked` replaced, so the
+    /// chunked versions can be checked against it on arbitrary buffers.
+    fn extend_forward_scalar(a: &[u8], a_pos: usize, b: &[u8], b_pos: usize) -> usize {
+        let mut fwd = 0;
+        while a_pos + fwd < a.len() && b_pos + fwd < b.len() && a[a_pos + fwd] == b[b_pos + fwd] {
+            fwd += 1;
+        }
+        fwd
+    }
+
+    fn extend_backward_scalar(a: &[u8], a_pos: usize, b: &[u8], b_pos: usize) -> usize {
+        let mut bwd = 0;
+        while a_pos > bwd && b_pos > bwd && a[a_pos - bwd - 1] == b[b_pos - bwd - 1] {
+            bwd += 1;
+        }
+        bwd
+    }
+
+    #[test]
+    fn test_extend_forward_chunked_matches_scalar_on_random_buffers() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..200 {
+            let len = rng.gen_range(0..200);
+            let a: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let mut b = a.clone();
+            // Flip one byte so extension has somewhere to stop, unless the
+            // buffer is empty or the flip happens to land past both ends.
+            if !b.is_empty() {
+                let idx = rng.gen_range(0..b.len());
+                b[idx] ^= 0xFF;
+            }
+            let a_pos = if a.is_empty() { 0 } else { rng.gen_range(0..a.len()) };
+            let b_pos = if b.is_empty() { 0 } else { rng.gen_range(0..b.len()) };
+            assert_eq!(
+                extend_forward_chunked(&a, a_pos, &b, b_pos),
+                extend_forward_scalar(&a, a_pos, &b, b_pos),
+                "a_pos={a_pos} b_pos={b_pos} len={len}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extend_backward_chunked_matches_scalar_on_random_buffers() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..200 {
+            let len = rng.gen_range(0..200);
+            let a: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let mut b = a.clone();
+            if !b.is_empty() {
+                let idx = rng.gen_range(0..b.len());
+                b[idx] ^= 0xFF;
+            }
+            let a_pos = if a.is_empty() { 0 } else { rng.gen_range(0..=a.len()) };
+            let b_pos = if b.is_empty() { 0 } else { rng.gen_range(0..=b.len()) };
+            assert_eq!(
+                extend_backward_chunked(&a, a_pos, &b, b_pos),
+                extend_backward_scalar(&a, a_pos, &b, b_pos),
+                "a_pos={a_pos} b_pos={b_pos} len={len}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_extend_chunked_matches_scalar_on_repetitive_buffers() {
+        // Highly-repetitive content exercises runs that cross several 8-byte
+        // chunk boundaries in a row, unlike the random buffers above where a
+        // mismatch usually falls within the first chunk.
+        let a = b"abcdefgh".repeat(13);
+        let mut b = a.clone();
+        for idx in [3usize, 40, 90] {
+            b[idx] ^= 0xFF;
+        }
+        for a_pos in 0..a.len() {
+            for b_pos in 0..b.len() {
+                assert_eq!(
+                    extend_forward_chunked(&a, a_pos, &b, b_pos),
+                    extend_forward_scalar(&a, a_pos, &b, b_pos),
+                    "forward a_pos={a_pos} b_pos={b_pos}"
+                );
+                assert_eq!(
+                    extend_backward_chunked(&a, a_pos, &b, b_pos),
+                    extend_backward_scalar(&a, a_pos, &b, b_pos),
+                    "backward a_pos={a_pos} b_pos={b_pos}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_extend_chunked_identical_buffers_run_to_completion() {
+        let a = vec![0x5Au8; 37];
+        assert_eq!(extend_forward_chunked(&a, 0, &a, 0), a.len());
+        assert_eq!(extend_backward_chunked(&a, a.len(), &a, a.len()), a.len());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::hash::{fingerprint, next_prime, RollingHash};
 use crate::splay::SplayTree;
-use crate::types::{Command, SEED_LEN, TABLE_SIZE};
+use crate::types::{Command, DiffOptions};
 
 /// One-Pass algorithm (Section 4.1, Figure 3).
 ///
@@ -13,27 +13,49 @@ use crate::types::{Command, SEED_LEN, TABLE_SIZE};
 /// Suboptimal on transpositions: cannot match blocks that appear in
 /// different order between R and V (Section 4.3).
 ///
-/// The hash table is auto-sized to max(q, num_seeds / p) so that large
+/// The hash table is auto-sized to max(q, num_r_seeds / p) so that large
 /// inputs get one slot per seed-length chunk of R.  TABLE_SIZE acts as a
-/// floor for small files.
-pub fn diff_onepass(r: &[u8], v: &[u8], p: usize, q: usize, verbose: bool, use_splay: bool, min_copy: usize) -> Vec<Command> {
+/// floor for small files; `opts.max_table` caps the auto-sized table.
+///
+/// `opts.anchor_blocks` trades match coverage for memory: R is indexed only
+/// at non-overlapping p-byte block boundaries (0, p, 2p, …) instead of every
+/// position, shrinking `num_r_seeds` — and so `h_r_ht` and its stored-offset
+/// count — by a factor of p. V is unaffected and still scanned/rolled at
+/// every position; a match is only ever found if it happens to land on one
+/// of R's block boundaries, which is common when edits don't shift block
+/// alignment (e.g. appends, or edits confined to one block).
+pub fn diff_onepass(r: &[u8], v: &[u8], opts: &DiffOptions) -> Vec<Command> {
+    let verbose = opts.verbose;
+    let use_splay = opts.use_splay;
+    let anchor_blocks = opts.anchor_blocks;
     let mut commands = Vec::new();
     if v.is_empty() {
         return commands;
     }
     // --min-copy raises the seed length so we never fingerprint at a
     // granularity finer than the minimum copy threshold.
-    let p = if min_copy > 0 { p.max(min_copy) } else { p };
+    let p = if opts.min_copy > 0 { opts.p.max(opts.min_copy) } else { opts.p };
 
-    // Auto-size hash table: one slot per p-byte chunk of R (floor = q).
-    let num_seeds = if r.len() >= p { r.len() - p + 1 } else { 0 };
-    let q = next_prime(q.max(num_seeds / p));
+    // Auto-size hash table: one slot per p indexed R seeds (floor = q,
+    // ceiling = max_table). `anchor_blocks` only indexes R at non-overlapping block
+    // boundaries 0, p, 2p, … (an rsync-style block signature) instead of
+    // every position, so its seed count — and hence `h_r_ht` — is smaller by
+    // a factor of p.
+    let num_r_seeds = if r.len() < p {
+        0
+    } else if anchor_blocks {
+        (r.len() - p) / p + 1
+    } else {
+        r.len() - p + 1
+    };
+    let q = next_prime(opts.q.max(num_r_seeds / p).min(opts.max_table));
 
     if verbose {
         eprintln!(
-            "onepass: {}, q={}, |R|={}, |V|={}, seed_len={}",
+            "onepass: {}{}, q={}, |R|={}, |V|={}, seed_len={}, r_seeds={}",
             if use_splay { "splay tree" } else { "hash table" },
-            q, r.len(), v.len(), p
+            if anchor_blocks { " [anchor-blocks]" } else { "" },
+            q, r.len(), v.len(), p, num_r_seeds
         );
     }
 
@@ -119,7 +141,11 @@ pub fn diff_onepass(r: &[u8], v: &[u8], p: usize, q: usize, verbose: bool, use_s
         } else {
             None
         };
-        let fp_r = if can_r {
+        // When anchoring, R is only ever fingerprinted/stored/looked-up at
+        // block-boundary positions; V keeps scanning (and rolling) every
+        // position regardless (`fp_v` above is untouched by this gate).
+        let r_is_anchor = !anchor_blocks || r_c % p == 0;
+        let fp_r = if can_r && r_is_anchor {
             if let Some(ref mut rh) = rh_r {
                 if r_c == rh_r_pos {
                     // Already at the right position
@@ -252,7 +278,7 @@ pub fn diff_onepass(r: &[u8], v: &[u8], p: usize, q: usize, verbose: bool, use_s
         let mut num_adds: usize = 0;
         for cmd in &commands {
             match cmd {
-                Command::Copy { length, .. } => {
+                Command::Copy { length, .. } | Command::CopyOut { length, .. } => {
                     total_copy += length;
                     num_copies += 1;
                     copy_lens.push(*length);
@@ -261,6 +287,10 @@ pub fn diff_onepass(r: &[u8], v: &[u8], p: usize, q: usize, verbose: bool, use_s
                     total_add += data.len();
                     num_adds += 1;
                 }
+                Command::Run { length, .. } => {
+                    total_add += length;
+                    num_adds += 1;
+                }
             }
         }
         let hit_pct = if dbg_lookups > 0 { dbg_matches as f64 / dbg_lookups as f64 * 100.0 } else { 0.0 };
@@ -292,10 +322,10 @@ pub fn diff_onepass(r: &[u8], v: &[u8], p: usize, q: usize, verbose: bool, use_s
         }
     }
 
-    commands
+    super::runify(commands)
 }
 
 /// Convenience wrapper with default parameters.
 pub fn diff_onepass_default(r: &[u8], v: &[u8]) -> Vec<Command> {
-    diff_onepass(r, v, SEED_LEN, TABLE_SIZE, false, false, 0)
+    diff_onepass(r, v, &DiffOptions::default())
 }
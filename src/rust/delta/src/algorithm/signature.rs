@@ -0,0 +1,241 @@
+//! Two-party remote delta (rsync-style): a `Signature` of R computed on
+//! whichever side holds R, shipped to the side holding V, which then diffs
+//! against the signature alone — `diff_from_signature` never touches R's
+//! bytes.
+//!
+//! Every other matching mode in this crate (`greedy`, `onepass`,
+//! `correcting`, `cdc`) assumes `r` and `v` are both in memory and verifies
+//! a weak-fingerprint hit with a direct byte comparison. That's unavailable
+//! here, so `signature` additionally records a collision-resistant strong
+//! digest (`digest::shake128_16`) per block, and `diff_from_signature`
+//! trusts a weak hit only once the strong digest of the candidate V window
+//! also matches.
+
+use std::collections::HashMap;
+
+use crate::digest::shake128_16;
+use crate::hash::{fingerprint, RollingHash};
+use crate::types::{Command, DiffOptions, DELTA_HASH_LEN};
+
+/// One block's signature entry: its index within R (so a hit recovers
+/// `index * block_size` as the Copy offset) and strong digest.
+#[derive(Clone, Debug)]
+struct Block {
+    index: usize,
+    strong: [u8; DELTA_HASH_LEN],
+}
+
+/// A compact stand-in for R: non-overlapping `block_size`-byte blocks (the
+/// final block short if `r.len()` isn't a multiple), each keyed by its weak
+/// Karp-Rabin fingerprint for O(1) average lookup, carrying a strong digest
+/// to confirm any weak hit.
+#[derive(Clone, Debug)]
+pub struct Signature {
+    block_size: usize,
+    r_len: usize,
+    by_weak: HashMap<u64, Vec<Block>>,
+}
+
+impl Signature {
+    /// The block size this signature was built with — `diff_from_signature`
+    /// scans V with a window of this width.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Length of the block at `index` — `block_size`, except the final
+    /// block of R which may be shorter.
+    fn block_len(&self, index: usize) -> usize {
+        let start = index * self.block_size;
+        self.r_len.saturating_sub(start).min(self.block_size)
+    }
+}
+
+/// Split `r` into non-overlapping `block_size`-byte blocks and record each
+/// one's weak fingerprint (`hash::fingerprint`) and strong digest
+/// (`digest::shake128_16`). `block_size` is floored at 1 byte.
+pub fn signature(r: &[u8], block_size: usize) -> Signature {
+    let block_size = block_size.max(1);
+    let mut by_weak: HashMap<u64, Vec<Block>> = HashMap::new();
+
+    let mut index = 0usize;
+    let mut start = 0usize;
+    while start < r.len() {
+        let end = (start + block_size).min(r.len());
+        let weak = fingerprint(r, start, end - start);
+        let strong = shake128_16(&r[start..end]);
+        by_weak.entry(weak).or_default().push(Block { index, strong });
+        index += 1;
+        start = end;
+    }
+
+    Signature { block_size, r_len: r.len(), by_weak }
+}
+
+/// Diff `v` against a `Signature` of R without R itself present.
+///
+/// Scans V with a `RollingHash` window of `sig.block_size()` (shrinking to
+/// whatever remains once fewer than a full block is left), one byte at a
+/// time. At each position, a weak-fingerprint hit is confirmed by strong
+/// digest before being trusted — mandatory here, since there's no R to
+/// byte-compare against. A confirmed match flushes any buffered literal
+/// bytes as an `Add`, then either extends the previous `Copy` (if it ended
+/// exactly where this block's R offset begins and no literal intervened —
+/// consecutive R blocks reappearing in order in V) or emits a new one; the
+/// window then jumps past the matched bytes. Unmatched bytes accumulate
+/// into the pending literal, flushed as a final `Add` once V is exhausted.
+pub fn diff_from_signature(sig: &Signature, v: &[u8], opts: &DiffOptions) -> Vec<Command> {
+    let verbose = opts.verbose;
+    let mut commands = Vec::new();
+    if v.is_empty() {
+        return commands;
+    }
+
+    let block_size = sig.block_size;
+    let mut literal_start = 0usize;
+    let mut pos = 0usize;
+
+    let mut rh: Option<RollingHash> = None;
+    let mut rh_pos = 0usize;
+
+    let mut dbg_lookups = 0usize;
+    let mut dbg_matches = 0usize;
+
+    while pos < v.len() {
+        let window = block_size.min(v.len() - pos);
+
+        let weak = match rh {
+            Some(ref mut h) if pos == rh_pos + 1 && window == block_size => {
+                h.roll(v[pos - 1], v[pos + window - 1]);
+                rh_pos = pos;
+                h.value()
+            }
+            Some(ref h) if pos == rh_pos => h.value(),
+            _ => {
+                let h = RollingHash::new(v, pos, window);
+                let val = h.value();
+                rh_pos = pos;
+                rh = Some(h);
+                val
+            }
+        };
+
+        let mut matched: Option<usize> = None;
+        if let Some(candidates) = sig.by_weak.get(&weak) {
+            dbg_lookups += 1;
+            let strong = shake128_16(&v[pos..pos + window]);
+            matched = candidates
+                .iter()
+                .find(|b| sig.block_len(b.index) == window && b.strong == strong)
+                .map(|b| b.index);
+        }
+
+        if let Some(index) = matched {
+            dbg_matches += 1;
+            if literal_start < pos {
+                commands.push(Command::Add { data: v[literal_start..pos].to_vec() });
+            }
+            let r_off = index * block_size;
+            let extended = match commands.last_mut() {
+                Some(Command::Copy { offset, length }) if *offset + *length == r_off => {
+                    *length += window;
+                    true
+                }
+                _ => false,
+            };
+            if !extended {
+                commands.push(Command::Copy { offset: r_off, length: window });
+            }
+            pos += window;
+            literal_start = pos;
+            rh = None;
+        } else {
+            pos += 1;
+        }
+    }
+
+    if literal_start < v.len() {
+        commands.push(Command::Add { data: v[literal_start..].to_vec() });
+    }
+
+    if verbose {
+        eprintln!(
+            "signature: block_size={}, |R blocks|={}, {} lookups, {} matches",
+            block_size,
+            sig.by_weak.values().map(Vec::len).sum::<usize>(),
+            dbg_lookups,
+            dbg_matches,
+        );
+    }
+
+    super::runify(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply_delta;
+
+    #[test]
+    fn test_signature_roundtrip_identical() {
+        let r = b"the quick brown fox jumps over the lazy dog, again and again".to_vec();
+        let sig = signature(&r, 8);
+        let cmds = diff_from_signature(&sig, &r, &DiffOptions::default());
+        assert_eq!(apply_delta(&r, &cmds), r);
+    }
+
+    #[test]
+    fn test_signature_coalesces_consecutive_blocks() {
+        let r = b"0123456789ABCDEF".to_vec();
+        let sig = signature(&r, 4);
+        let cmds = diff_from_signature(&sig, &r, &DiffOptions::default());
+        let copies: Vec<_> = cmds
+            .iter()
+            .filter(|c| matches!(c, Command::Copy { .. }))
+            .collect();
+        assert_eq!(copies.len(), 1, "all 4 blocks should coalesce into one Copy");
+        assert!(matches!(copies[0], Command::Copy { offset: 0, length: 16 }));
+        assert_eq!(apply_delta(&r, &cmds), r);
+    }
+
+    #[test]
+    fn test_signature_trailing_partial_block() {
+        let r = b"0123456789ABCDEFGH".to_vec(); // 18 bytes: 4 full blocks + a 2-byte tail
+        let sig = signature(&r, 4);
+        let cmds = diff_from_signature(&sig, &r, &DiffOptions::default());
+        assert_eq!(apply_delta(&r, &cmds), r);
+    }
+
+    #[test]
+    fn test_signature_detects_literal_insertion() {
+        let r: Vec<u8> = (0..64u8).collect();
+        let mut v = r.clone();
+        v.insert(10, 0xFF);
+        let sig = signature(&r, 8);
+        let cmds = diff_from_signature(&sig, &v, &DiffOptions::default());
+        assert!(cmds.iter().any(|c| matches!(c, Command::Add { .. })));
+        assert_eq!(apply_delta(&r, &cmds), v);
+    }
+
+    #[test]
+    fn test_signature_empty_version() {
+        let sig = signature(b"reference material", 4);
+        let cmds = diff_from_signature(&sig, b"", &DiffOptions::default());
+        assert!(cmds.is_empty());
+    }
+
+    #[test]
+    fn test_signature_no_match_is_all_add() {
+        let sig = signature(b"aaaaaaaaaaaaaaaaaaaaaaaa", 8);
+        let v = b"zzzzzzzzzzzzzzzzzzzzzzzz".to_vec();
+        let cmds = diff_from_signature(&sig, &v, &DiffOptions::default());
+        assert_eq!(apply_delta(b"aaaaaaaaaaaaaaaaaaaaaaaa", &cmds), v);
+        assert!(!cmds.iter().any(|c| matches!(c, Command::Copy { .. })));
+    }
+
+    #[test]
+    fn test_signature_block_size_accessor() {
+        let sig = signature(b"0123456789", 4);
+        assert_eq!(sig.block_size(), 4);
+    }
+}
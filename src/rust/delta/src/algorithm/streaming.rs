@@ -0,0 +1,253 @@
+//! Windowed differencing over `Read` streams for inputs larger than memory.
+//!
+//! `diff_streaming` mirrors the One-Pass algorithm's lockstep scan
+//! (`onepass::diff_onepass`, Section 4.1) but keeps only a bounded
+//! `window_bytes` suffix of each stream resident at a time, reading more
+//! from the underlying `Read` as the matching cursors advance.  A seed's
+//! offset is only retained in the lookup tables while its window is
+//! resident; once a window slides past it the entry is evicted and any
+//! would-be `Copy` back into that region is naturally encoded as `Add`
+//! instead (there is nothing left to re-resolve against).
+
+use std::collections::VecDeque;
+use std::io::Read;
+
+use crate::hash::{fingerprint, next_prime};
+use crate::types::{Command, DiffOptions};
+
+/// A byte stream cursor: a `VecDeque` buffer backed by a `Read`, with
+/// `base` tracking the global offset of the buffer's front byte.
+struct Window<S> {
+    source: S,
+    buf: VecDeque<u8>,
+    base: usize,
+    eof: bool,
+}
+
+impl<S: Read> Window<S> {
+    fn new(source: S) -> Self {
+        Window { source, buf: VecDeque::new(), base: 0, eof: false }
+    }
+
+    /// Make sure at least `want` bytes starting at global offset `self.base`
+    /// are buffered (or the stream is exhausted trying).
+    fn ensure(&mut self, want: usize) {
+        let mut chunk = [0u8; 64 * 1024];
+        while self.buf.len() < want && !self.eof {
+            match self.source.read(&mut chunk) {
+                Ok(0) | Err(_) => {
+                    self.eof = true;
+                }
+                Ok(n) => self.buf.extend(chunk[..n].iter().copied()),
+            }
+        }
+    }
+
+    /// Global offset one past the last buffered byte.
+    fn buffered_end(&self) -> usize {
+        self.base + self.buf.len()
+    }
+
+    /// Drop buffered bytes before global offset `keep_from`, advancing `base`.
+    fn evict_before(&mut self, keep_from: usize) {
+        if keep_from > self.base {
+            let drop_n = (keep_from - self.base).min(self.buf.len());
+            self.buf.drain(..drop_n);
+            self.base += drop_n;
+        }
+    }
+
+    /// Byte at global offset `pos`, assuming it is currently buffered.
+    #[inline]
+    fn at(&self, pos: usize) -> u8 {
+        self.buf[pos - self.base]
+    }
+
+    fn has(&self, pos: usize) -> bool {
+        pos >= self.base && pos < self.buffered_end()
+    }
+
+    fn seed(&mut self, pos: usize, p: usize) -> Option<u64> {
+        self.ensure(pos - self.base + p);
+        if pos + p > self.buffered_end() {
+            return None;
+        }
+        // `VecDeque` may be split across two halves; copy the seed out
+        // rather than special-casing contiguity, since `p` is small.
+        let mut tmp = Vec::with_capacity(p);
+        for i in 0..p {
+            tmp.push(self.at(pos + i));
+        }
+        Some(fingerprint(&tmp, 0, p))
+    }
+}
+
+/// Windowed driver for memory-bounded differencing.
+///
+/// Slides a `window_bytes` suffix of `reference` and of `version` forward
+/// in lockstep, matching seeds the same way `diff_onepass` does, and emits
+/// `Command`s with *absolute* offsets into the full (unbuffered) reference.
+/// The result is an ordinary `Vec<Command>`/iterator so it drops straight
+/// into the existing `place_commands` → `encode_delta` → `apply_delta`
+/// pipeline.
+pub fn diff_streaming<R: Read, V: Read>(
+    reference: R,
+    version: V,
+    opts: &DiffOptions,
+    window_bytes: usize,
+) -> impl Iterator<Item = Command> {
+    let p = opts.p.max(1);
+    let window_bytes = window_bytes.max(p * 4);
+
+    let mut r_win = Window::new(reference);
+    let mut v_win = Window::new(version);
+
+    // Fixed-size, retain-existing, version-flushed hash tables — the same
+    // scheme as `diff_onepass` — sized off `window_bytes` rather than the
+    // (unknown) total input length, so table memory stays bounded no
+    // matter how large the streams are. Entries store *global* offsets,
+    // so an entry surviving past its window's eviction is simply ignored
+    // once `Window::has` reports the offset is no longer buffered.
+    let q = next_prime(opts.q.max(window_bytes / p).min(opts.max_table));
+    let mut h_r: Vec<Option<(u64, usize, u64)>> = vec![None; q];
+    let mut h_v: Vec<Option<(u64, usize, u64)>> = vec![None; q];
+    let mut ver: u64 = 0;
+
+    #[inline]
+    fn ht_get(table: &[Option<(u64, usize, u64)>], fp: u64, q: usize, ver: u64) -> Option<usize> {
+        match table[(fp % q as u64) as usize] {
+            Some((stored_fp, offset, stored_ver)) if stored_ver == ver && stored_fp == fp => Some(offset),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn ht_put(table: &mut [Option<(u64, usize, u64)>], fp: u64, off: usize, q: usize, ver: u64) {
+        let idx = (fp % q as u64) as usize;
+        if let Some((_, _, stored_ver)) = table[idx] {
+            if stored_ver == ver {
+                return; // retain-existing policy
+            }
+        }
+        table[idx] = Some((fp, off, ver));
+    }
+
+    let mut commands = Vec::new();
+    let mut r_c: usize = 0;
+    let mut v_c: usize = 0;
+    let mut v_s: usize = 0;
+
+    loop {
+        r_win.ensure(r_c - r_win.base + p);
+        v_win.ensure(v_c - v_win.base + p);
+
+        let can_r = r_win.has(r_c) && r_c + p <= r_win.buffered_end();
+        let can_v = v_win.has(v_c) && v_c + p <= v_win.buffered_end();
+        if !can_r && !can_v {
+            break;
+        }
+
+        let fp_r = if can_r { r_win.seed(r_c, p) } else { None };
+        let fp_v = if can_v { v_win.seed(v_c, p) } else { None };
+
+        if let Some(fp) = fp_r {
+            ht_put(&mut h_r, fp, r_c, q, ver);
+        }
+        if let Some(fp) = fp_v {
+            ht_put(&mut h_v, fp, v_c, q, ver);
+        }
+
+        let mut found: Option<(usize, usize)> = None; // (r_m, v_m)
+        if let Some(fp) = fp_r {
+            if let Some(v_cand) = ht_get(&h_v, fp, q, ver) {
+                // A periodic Add flush moves `v_s` without bumping `ver`, so
+                // an entry below `v_s` is already encoded and can't be copied.
+                if v_cand >= v_s && v_win.has(v_cand) && (0..p).all(|i| r_win.at(r_c + i) == v_win.at(v_cand + i)) {
+                    found = Some((r_c, v_cand));
+                }
+            }
+        }
+        if found.is_none() {
+            if let Some(fp) = fp_v {
+                if let Some(r_cand) = ht_get(&h_r, fp, q, ver) {
+                    if r_win.has(r_cand) && (0..p).all(|i| v_win.at(v_c + i) == r_win.at(r_cand + i)) {
+                        found = Some((r_cand, v_c));
+                    }
+                }
+            }
+        }
+
+        let Some((r_m, v_m)) = found else {
+            r_c += 1;
+            // Once V is fully read, there is nothing left to match or flush
+            // against it; keep scanning R (if it still has data) but stop
+            // advancing v_c past what was ever buffered.
+            let v_exhausted = v_win.eof && v_c >= v_win.buffered_end();
+            if !v_exhausted {
+                v_c += 1;
+            }
+            // A long unmatched run would otherwise keep `v_s` pinned at its
+            // last match and grow `v_win`'s buffer without bound; flush it as an Add
+            // every `window_bytes` so eviction can make progress.
+            if !v_exhausted && v_c - v_s >= window_bytes {
+                let mut add = Vec::with_capacity(v_c - v_s);
+                for i in v_s..v_c {
+                    v_win.ensure(i - v_win.base + 1);
+                    add.push(v_win.at(i));
+                }
+                commands.push(Command::Add { data: add });
+                v_s = v_c;
+            }
+            r_win.evict_before(r_c.saturating_sub(window_bytes));
+            v_win.evict_before(v_s.saturating_sub(window_bytes));
+            continue;
+        };
+
+        // Extend forward while both sides remain buffered (or can be topped up).
+        let mut ml = 0usize;
+        loop {
+            r_win.ensure(r_m + ml - r_win.base + 1);
+            v_win.ensure(v_m + ml - v_win.base + 1);
+            if !r_win.has(r_m + ml) || !v_win.has(v_m + ml) {
+                break;
+            }
+            if r_win.at(r_m + ml) != v_win.at(v_m + ml) {
+                break;
+            }
+            ml += 1;
+        }
+
+        if v_s < v_m {
+            let mut add = Vec::with_capacity(v_m - v_s);
+            for i in v_s..v_m {
+                v_win.ensure(i - v_win.base + 1);
+                add.push(v_win.at(i));
+            }
+            commands.push(Command::Add { data: add });
+        }
+        commands.push(Command::Copy { offset: r_m, length: ml });
+        v_s = v_m + ml;
+        r_c = r_m + ml;
+        v_c = v_m + ml;
+        ver += 1;
+
+        r_win.evict_before(r_c.saturating_sub(window_bytes));
+        v_win.evict_before(v_s.saturating_sub(window_bytes));
+    }
+
+    // Flush any unmatched tail of V.
+    let mut tail = Vec::new();
+    loop {
+        v_win.ensure(v_s - v_win.base + 1);
+        if !v_win.has(v_s) {
+            break;
+        }
+        tail.push(v_win.at(v_s));
+        v_s += 1;
+    }
+    if !tail.is_empty() {
+        commands.push(Command::Add { data: tail });
+    }
+
+    super::runify(commands).into_iter()
+}
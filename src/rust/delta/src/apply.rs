@@ -1,4 +1,6 @@
-use crate::types::{Command, PlacedCommand};
+use crate::digest::shake128_n;
+use crate::encoding::decode_delta;
+use crate::types::{ApplyError, Command, DeltaError, PlacedCommand};
 
 /// Compute the total output size of algorithm commands.
 pub fn output_size(commands: &[Command]) -> usize {
@@ -6,7 +8,9 @@ pub fn output_size(commands: &[Command]) -> usize {
         .iter()
         .map(|cmd| match cmd {
             Command::Copy { length, .. } => *length,
+            Command::CopyOut { length, .. } => *length,
             Command::Add { data } => data.len(),
+            Command::Run { length, .. } => *length,
         })
         .sum()
 }
@@ -25,6 +29,14 @@ pub fn place_commands(commands: &[Command]) -> Vec<PlacedCommand> {
                 });
                 dst += length;
             }
+            Command::CopyOut { offset, length } => {
+                placed.push(PlacedCommand::CopyOut {
+                    src: *offset,
+                    dst,
+                    length: *length,
+                });
+                dst += length;
+            }
             Command::Add { data } => {
                 placed.push(PlacedCommand::Add {
                     dst,
@@ -32,50 +44,139 @@ pub fn place_commands(commands: &[Command]) -> Vec<PlacedCommand> {
                 });
                 dst += data.len();
             }
+            Command::Run { byte, length } => {
+                placed.push(PlacedCommand::Run {
+                    dst,
+                    byte: *byte,
+                    length: *length,
+                });
+                dst += length;
+            }
         }
     }
     placed
 }
 
+/// Inverse of `place_commands`: drop the explicit destination offsets,
+/// recovering the sequential algorithm output.
+///
+/// Commands must already be in destination order (as produced by
+/// `place_commands`, or by `decode_delta` on a standard — non-in-place —
+/// delta); `make_inplace` may reorder copies, so its output is not a valid
+/// input here.
+pub fn unplace_commands(commands: &[PlacedCommand]) -> Vec<Command> {
+    commands
+        .iter()
+        .map(|cmd| match cmd {
+            PlacedCommand::Copy { src, length, .. } => Command::Copy {
+                offset: *src,
+                length: *length,
+            },
+            PlacedCommand::CopyOut { src, length, .. } => Command::CopyOut {
+                offset: *src,
+                length: *length,
+            },
+            PlacedCommand::Add { data, .. } => Command::Add { data: data.clone() },
+            PlacedCommand::Run { byte, length, .. } => Command::Run {
+                byte: *byte,
+                length: *length,
+            },
+        })
+        .collect()
+}
+
+/// Copy `length` bytes within `out` from `src` to `dst`, forward and
+/// byte-by-byte.
+///
+/// Used for every `PlacedCommand::CopyOut`, standard or in-place, whose
+/// `src` may still be within `length` bytes of `dst` (the classic LZ77
+/// self-overlapping copy, used to expand a short repeating pattern): a bulk
+/// `copy_from_slice` can't alias `out` with itself, and a bulk `memmove`
+/// (`copy_within`, as used for `Copy`) would also be wrong here since
+/// `src < dst` always holds and the overlap is deliberate — each output byte
+/// must become visible to later reads in the same command before the loop
+/// moves on, so forward order is required.
+fn copy_out_forward(out: &mut [u8], src: usize, dst: usize, length: usize) {
+    for i in 0..length {
+        out[dst + i] = out[src + i];
+    }
+}
+
+/// Apply one placed command in standard mode: read from R (or, for
+/// `CopyOut`, from the output reconstructed so far), write to out.
+///
+/// Returns the end offset the command wrote up to, for tracking the overall
+/// high-water mark across a stream of commands (see `apply_placed_to`).
+///
+/// `commands` must be in increasing `dst` order for `CopyOut` to read valid
+/// data — true of `place_commands`' output and of a standard (non-in-place)
+/// delta's command stream, since both preserve emission order.
+pub fn apply_placed_command_to(r: &[u8], cmd: &PlacedCommand, out: &mut [u8]) -> usize {
+    match cmd {
+        PlacedCommand::Copy { src, dst, length, .. } => {
+            out[*dst..*dst + *length].copy_from_slice(&r[*src..*src + *length]);
+            dst + length
+        }
+        PlacedCommand::CopyOut { src, dst, length } => {
+            copy_out_forward(out, *src, *dst, *length);
+            dst + length
+        }
+        PlacedCommand::Add { dst, data } => {
+            out[*dst..*dst + data.len()].copy_from_slice(data);
+            dst + data.len()
+        }
+        PlacedCommand::Run { dst, byte, length } => {
+            out[*dst..*dst + *length].fill(*byte);
+            dst + length
+        }
+    }
+}
+
 /// Apply placed commands in standard mode: read from R, write to out.
 ///
 /// Returns the number of bytes written.
 pub fn apply_placed_to(r: &[u8], commands: &[PlacedCommand], out: &mut [u8]) -> usize {
     let mut max_written = 0;
     for cmd in commands {
-        match cmd {
-            PlacedCommand::Copy { src, dst, length } => {
-                out[*dst..*dst + *length].copy_from_slice(&r[*src..*src + *length]);
-                let end = dst + length;
-                if end > max_written {
-                    max_written = end;
-                }
-            }
-            PlacedCommand::Add { dst, data } => {
-                out[*dst..*dst + data.len()].copy_from_slice(data);
-                let end = dst + data.len();
-                if end > max_written {
-                    max_written = end;
-                }
-            }
+        let end = apply_placed_command_to(r, cmd, out);
+        if end > max_written {
+            max_written = end;
         }
     }
     max_written
 }
 
+/// Apply one placed in-place command within a single buffer.
+///
+/// `Copy` always moves via `copy_within` (`slice::copy_within`, a true
+/// memmove), which handles every overlap direction between `src` and `dst`
+/// correctly on its own. `CopyOut` cannot use the same trick — when its
+/// source overlaps its own destination it is expanding a repeating pattern
+/// (see `copy_out_forward`), which depends on each output byte becoming
+/// visible to later reads within the *same* command; a bulk move would
+/// instead duplicate the pre-call bytes verbatim, silently dropping that
+/// repetition.
+pub fn apply_placed_inplace_command_to(cmd: &PlacedCommand, buf: &mut [u8]) {
+    match cmd {
+        PlacedCommand::Copy { src, dst, length } => buf.copy_within(*src..*src + *length, *dst),
+        PlacedCommand::CopyOut { src, dst, length } => {
+            copy_out_forward(buf, *src, *dst, *length);
+        }
+        PlacedCommand::Add { dst, data } => {
+            buf[*dst..*dst + data.len()].copy_from_slice(data);
+        }
+        PlacedCommand::Run { dst, byte, length } => {
+            buf[*dst..*dst + *length].fill(*byte);
+        }
+    }
+}
+
 /// Apply placed commands in-place within a single buffer.
 ///
 /// Uses `copy_within` (maps to libc `memmove`) so overlapping src/dst is safe.
 pub fn apply_placed_inplace_to(commands: &[PlacedCommand], buf: &mut [u8]) {
     for cmd in commands {
-        match cmd {
-            PlacedCommand::Copy { src, dst, length } => {
-                buf.copy_within(*src..*src + *length, *dst);
-            }
-            PlacedCommand::Add { dst, data } => {
-                buf[*dst..*dst + data.len()].copy_from_slice(data);
-            }
-        }
+        apply_placed_inplace_command_to(cmd, buf);
     }
 }
 
@@ -96,6 +197,14 @@ pub fn apply_delta_to(r: &[u8], commands: &[Command], out: &mut [u8]) -> usize {
                 out[pos..pos + *length].copy_from_slice(&r[*offset..*offset + *length]);
                 pos += *length;
             }
+            Command::CopyOut { offset, length } => {
+                copy_out_forward(out, *offset, pos, *length);
+                pos += *length;
+            }
+            Command::Run { byte, length } => {
+                out[pos..pos + *length].fill(*byte);
+                pos += *length;
+            }
         }
     }
     pos
@@ -116,8 +225,167 @@ pub fn apply_delta_inplace(
 ) -> Vec<u8> {
     let buf_size = r.len().max(version_size);
     let mut buf = vec![0u8; buf_size];
-    buf[..r.len()].copy_from_slice(r);
-    apply_placed_inplace_to(commands, &mut buf);
-    buf.truncate(version_size);
+    let written = apply_delta_inplace_into(r, commands, version_size, &mut buf)
+        .expect("buf sized to r.len().max(version_size) is always large enough");
+    buf.truncate(written);
     buf
 }
+
+/// Allocation-free counterpart to `apply_delta_inplace`: writes the
+/// reconstructed version directly into the caller's `out` slice instead of
+/// returning a freshly allocated `Vec<u8>`, so in-place deltas can be
+/// applied on targets with no heap (e.g. a fixed buffer sized once at
+/// startup on an embedded target).
+///
+/// This only covers the apply side: `PlacedCommand`/`Command` themselves
+/// hold `Vec<u8>` payloads for `Add`, and `shake128_16`/`shake128_n`
+/// (`digest.rs`) return owned digests, so the crate as a whole still
+/// requires `alloc` and isn't buildable under `#![no_std]`. Gating that
+/// behind an `alloc` feature needs a `[features]` table, and this tree has
+/// no Cargo manifest to put one in (same situation as the `compress`
+/// feature ask noted on `Compressor` in `types.rs`) — this function is as
+/// far as allocation-free apply goes here.
+///
+/// `out` must be at least `reference.len().max(version_size)` bytes, since
+/// in-place reconstruction starts by seeding `out` with `reference` and
+/// then overwrites it in place following `commands` (produced by
+/// `make_inplace`, which already guarantees copies never read a byte after
+/// it has been overwritten by a command earlier in the sequence). Returns
+/// `Err(ApplyError::OutputTooSmall)` rather than panicking if `out` is
+/// undersized, and `Ok(version_size)` — the number of leading bytes of
+/// `out` that hold the reconstructed version — otherwise.
+pub fn apply_delta_inplace_into(
+    reference: &[u8],
+    commands: &[PlacedCommand],
+    version_size: usize,
+    out: &mut [u8],
+) -> Result<usize, ApplyError> {
+    let needed = reference.len().max(version_size);
+    if out.len() < needed {
+        return Err(ApplyError::OutputTooSmall {
+            needed,
+            available: out.len(),
+        });
+    }
+    out[..reference.len()].copy_from_slice(reference);
+    apply_placed_inplace_to(commands, &mut out[..needed]);
+    Ok(version_size)
+}
+
+// ── hash-verified decode + apply (untrusted deltas) ─────────────────────
+
+/// Decode `delta`'s header, mapping `decode_delta`'s generic parse errors
+/// onto the more specific variants `apply_delta_checked`/
+/// `apply_delta_inplace_checked` promise their callers.
+fn decode_checked_header(
+    delta: &[u8],
+) -> Result<(Vec<PlacedCommand>, bool, usize, Vec<u8>, Vec<u8>), DeltaError> {
+    decode_delta(delta).map_err(|e| match e {
+        DeltaError::UnexpectedEof => DeltaError::Truncated,
+        DeltaError::InvalidFormat(msg) => DeltaError::BadHeader(msg),
+        other => other,
+    })
+}
+
+fn verify_reference(reference: &[u8], src_hash: &[u8]) -> Result<(), DeltaError> {
+    if shake128_n(reference, src_hash.len()) != src_hash {
+        return Err(DeltaError::WrongReference);
+    }
+    Ok(())
+}
+
+fn verify_output(out: &[u8], dst_hash: &[u8]) -> Result<(), DeltaError> {
+    if shake128_n(out, dst_hash.len()) != dst_hash {
+        return Err(DeltaError::CorruptOutput);
+    }
+    Ok(())
+}
+
+/// Reject any command whose read or write range falls outside the buffers
+/// `apply_placed_to`/`apply_placed_inplace_to` will index into.
+///
+/// `copy_src_bound` is the length of the buffer `Copy.src` reads from
+/// (`reference.len()` in standard mode, the shared buffer's length in-place);
+/// `write_bound` is the length of the buffer everything else reads/writes
+/// (`version_size` standard, the shared buffer's length in-place — `CopyOut`
+/// reads from the output side in both modes). A delta's `src_hash`/`dst_hash`
+/// only attest to the reference and the final output, so a forged command
+/// stream that passes both hashes could still carry an out-of-range offset;
+/// this check runs before any command is applied.
+fn validate_placed(
+    placed: &[PlacedCommand],
+    copy_src_bound: usize,
+    write_bound: usize,
+) -> Result<(), DeltaError> {
+    let in_bounds = |offset: usize, length: usize, bound: usize| {
+        offset.checked_add(length).is_some_and(|end| end <= bound)
+    };
+    for cmd in placed {
+        let ok = match cmd {
+            PlacedCommand::Copy { src, dst, length, .. } => {
+                in_bounds(*src, *length, copy_src_bound) && in_bounds(*dst, *length, write_bound)
+            }
+            PlacedCommand::CopyOut { src, dst, length } => {
+                in_bounds(*src, *length, write_bound) && in_bounds(*dst, *length, write_bound)
+            }
+            PlacedCommand::Add { dst, data } => in_bounds(*dst, data.len(), write_bound),
+            PlacedCommand::Run { dst, length, .. } => in_bounds(*dst, *length, write_bound),
+        };
+        if !ok {
+            return Err(DeltaError::CommandOutOfBounds);
+        }
+    }
+    Ok(())
+}
+
+/// Decode a standard (non-in-place) `delta` and apply it to `reference`,
+/// verifying both integrity digests recorded in the header before trusting
+/// the bytes: `reference` is hashed and compared against `src_hash` before
+/// any command runs, and the reconstructed output is hashed and compared
+/// against `dst_hash` before it is returned.
+///
+/// Returns `Err(DeltaError::BadHeader(_))` if `delta` is in-place format —
+/// use `apply_delta_inplace_checked` for those, since in-place replay needs
+/// a single shared working buffer rather than a separate reference and
+/// output.
+pub fn apply_delta_checked(reference: &[u8], delta: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let (placed, is_inplace, version_size, src_hash, dst_hash) = decode_checked_header(delta)?;
+    if is_inplace {
+        return Err(DeltaError::BadHeader(
+            "delta is in-place format; use apply_delta_inplace_checked".into(),
+        ));
+    }
+    verify_reference(reference, &src_hash)?;
+    validate_placed(&placed, reference.len(), version_size)?;
+
+    let mut out = vec![0u8; version_size];
+    apply_placed_to(reference, &placed, &mut out);
+    verify_output(&out, &dst_hash)?;
+    Ok(out)
+}
+
+/// Decode an in-place `delta` and apply it against `reference`, verifying
+/// both integrity digests recorded in the header before trusting the
+/// bytes, the same way `apply_delta_checked` does for standard deltas.
+///
+/// Returns `Err(DeltaError::BadHeader(_))` if `delta` is standard format —
+/// use `apply_delta_checked` for those.
+pub fn apply_delta_inplace_checked(reference: &[u8], delta: &[u8]) -> Result<Vec<u8>, DeltaError> {
+    let (placed, is_inplace, version_size, src_hash, dst_hash) = decode_checked_header(delta)?;
+    if !is_inplace {
+        return Err(DeltaError::BadHeader(
+            "delta is standard format; use apply_delta_checked".into(),
+        ));
+    }
+    verify_reference(reference, &src_hash)?;
+
+    let buf_size = reference.len().max(version_size);
+    validate_placed(&placed, buf_size, buf_size)?;
+
+    let mut buf = vec![0u8; buf_size];
+    buf[..reference.len()].copy_from_slice(reference);
+    apply_placed_inplace_to(&placed, &mut buf);
+    buf.truncate(version_size);
+    verify_output(&buf, &dst_hash)?;
+    Ok(buf)
+}
@@ -0,0 +1,142 @@
+//! Pluggable fast, non-cryptographic block index for match-finding.
+//!
+//! `hash::fingerprint`/`RollingHash` use a Karp-Rabin polynomial over a
+//! Mersenne prime, built for O(1) incremental rolling, not raw per-window
+//! throughput. `BlockIndex` trades that incrementality for a cheaper
+//! multiply-xor-fold mixer (an aHash/FxHash-style keyed mix, without the
+//! AES-NI path those crates take on hardware that supports it) when a
+//! caller is going to hash each window from scratch rather than roll
+//! between adjacent ones. It is keyed per-build with a random `u64` so an
+//! adversarial input crafted against one table's collisions won't
+//! reproduce a worst-case chain against the next rebuild.
+//!
+//! Like the Karp-Rabin table, `BlockIndex` only narrows candidates: a
+//! caller must still byte-compare a query window against any offset it
+//! returns before trusting it as a match.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+
+/// Index of fixed-size (`window`-byte) windows of a reference buffer,
+/// keyed by a fast non-cryptographic hash.
+pub struct BlockIndex {
+    key: u64,
+    window: usize,
+    table: HashMap<u64, Vec<usize>>,
+}
+
+impl BlockIndex {
+    /// Build an index over the `window`-byte window at every offset in
+    /// `reference`, keyed with a fresh random key drawn for this build.
+    pub fn build(reference: &[u8], window: usize) -> Self {
+        let key = rand::thread_rng().gen();
+        Self::build_with_key(reference, window, key)
+    }
+
+    /// Same as `build`, but with an explicit key. Exposed so tests and
+    /// benchmarks can assert that the candidates returned by `query` — and
+    /// therefore the commands an algorithm derives from them — don't
+    /// depend on which key a given build happened to draw.
+    pub fn build_with_key(reference: &[u8], window: usize, key: u64) -> Self {
+        let mut table: HashMap<u64, Vec<usize>> = HashMap::new();
+        if window > 0 && reference.len() >= window {
+            for offset in 0..=(reference.len() - window) {
+                let h = mix_hash(&reference[offset..offset + window], key);
+                table.entry(h).or_default().push(offset);
+            }
+        }
+        BlockIndex { key, window, table }
+    }
+
+    /// The key this index was built with.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// The window width this index hashes.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Reference offsets whose window hashes the same as
+    /// `version[offset..offset + window]`. Empty if `offset` doesn't leave
+    /// room for a full window, or if nothing in the reference collides.
+    pub fn query(&self, version: &[u8], offset: usize) -> &[usize] {
+        if offset + self.window > version.len() {
+            return &[];
+        }
+        let h = mix_hash(&version[offset..offset + self.window], self.key);
+        self.table.get(&h).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+}
+
+/// Keyed, non-cryptographic mixer (aHash/FxHash-style fold-multiply-xor
+/// over 8-byte words).
+fn mix_hash(window: &[u8], key: u64) -> u64 {
+    // FxHash's odd multiplicative constant (derived from the golden ratio);
+    // any large odd constant works as the fold multiplier.
+    const FOLD: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+    let mut h = key;
+    let mut chunks = window.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        h = (h.rotate_left(5) ^ word).wrapping_mul(FOLD);
+    }
+    let rem = chunks.remainder();
+    if !rem.is_empty() {
+        let mut buf = [0u8; 8];
+        buf[..rem.len()].copy_from_slice(rem);
+        let word = u64::from_le_bytes(buf);
+        h = (h.rotate_left(5) ^ word).wrapping_mul(FOLD);
+    }
+    h ^ (h >> 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mix_hash_deterministic() {
+        assert_eq!(mix_hash(b"abcdefgh", 7), mix_hash(b"abcdefgh", 7));
+    }
+
+    #[test]
+    fn test_mix_hash_key_changes_output() {
+        assert_ne!(mix_hash(b"abcdefgh", 1), mix_hash(b"abcdefgh", 2));
+    }
+
+    #[test]
+    fn test_mix_hash_differs_on_different_input() {
+        assert_ne!(mix_hash(b"abcdefgh", 7), mix_hash(b"hgfedcba", 7));
+    }
+
+    #[test]
+    fn test_block_index_finds_exact_window() {
+        let r = b"the quick brown fox jumps over the lazy dog";
+        let idx = BlockIndex::build_with_key(r, 4, 42);
+        let candidates = idx.query(b"lazy", 0);
+        assert!(candidates.contains(&r.windows(4).position(|w| w == b"lazy").unwrap()));
+    }
+
+    #[test]
+    fn test_block_index_candidates_independent_of_key() {
+        let r = b"abcabcabcxyzabcabc";
+        let v = b"abc";
+        let by_offset_for_key = |key: u64| -> Vec<usize> {
+            let idx = BlockIndex::build_with_key(r, 3, key);
+            let mut offsets = idx.query(v, 0).to_vec();
+            offsets.sort();
+            offsets
+        };
+        assert_eq!(by_offset_for_key(0), by_offset_for_key(123456789));
+        assert_eq!(by_offset_for_key(1), by_offset_for_key(u64::MAX));
+    }
+
+    #[test]
+    fn test_block_index_out_of_range_query_is_empty() {
+        let idx = BlockIndex::build_with_key(b"reference", 4, 0);
+        assert!(idx.query(b"ab", 0).is_empty());
+    }
+}
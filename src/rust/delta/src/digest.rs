@@ -0,0 +1,63 @@
+//! SHAKE128 integrity digests (FIPS 202) for source/destination verification.
+//!
+//! The delta container records a digest of the reference and of the
+//! reconstructed version so a decoder can detect a mismatched reference
+//! file or a corrupted reconstruction before trusting its output.
+
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake128;
+
+use crate::types::DELTA_HASH_LEN;
+
+/// Squeeze `n` bytes of SHAKE128 output for `data`.
+///
+/// SHAKE128 is an extendable-output function (XOF): squeezing more bytes
+/// from the same sponge state only ever extends the output, so `shake128_n`
+/// with a larger `n` reproduces `shake128_n` with a smaller `n` as a prefix
+/// (in particular `shake128_n(data, 16) == shake128_16(data)`). This backs
+/// the v4 container's configurable `digest_len` header field.
+pub fn shake128_n(data: &[u8], n: usize) -> Vec<u8> {
+    let mut out = vec![0u8; n];
+    let mut hasher = Shake128::default();
+    hasher.update(data);
+    hasher.finalize_xof().read(&mut out);
+    out
+}
+
+/// Squeeze a 16-byte SHAKE128 digest of `data`.
+///
+/// SHAKE128 is an extendable-output function (XOF); 16 bytes is the
+/// default truncation used by the delta container format.
+pub fn shake128_16(data: &[u8]) -> [u8; DELTA_HASH_LEN] {
+    let mut out = [0u8; DELTA_HASH_LEN];
+    out.copy_from_slice(&shake128_n(data, DELTA_HASH_LEN));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shake128_16_deterministic() {
+        assert_eq!(shake128_16(b"hello"), shake128_16(b"hello"));
+    }
+
+    #[test]
+    fn test_shake128_16_differs() {
+        assert_ne!(shake128_16(b"hello"), shake128_16(b"world"));
+    }
+
+    #[test]
+    fn test_shake128_n_extends_shake128_16() {
+        // Squeezing more bytes from the same sponge only extends the output.
+        assert_eq!(&shake128_n(b"hello", 32)[..16], &shake128_16(b"hello")[..]);
+        assert_eq!(&shake128_n(b"hello", 64)[..32], &shake128_n(b"hello", 32)[..]);
+    }
+
+    #[test]
+    fn test_shake128_n_length() {
+        assert_eq!(shake128_n(b"hello", 32).len(), 32);
+        assert_eq!(shake128_n(b"hello", 64).len(), 64);
+    }
+}
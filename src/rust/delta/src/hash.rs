@@ -105,22 +105,78 @@ impl RollingHash {
 
 use rand::Rng;
 
-/// Modular exponentiation: base^exp mod modulus (uses u128 to avoid overflow).
-fn power_mod(base: u64, mut exp: u64, modulus: u64) -> u64 {
-    if modulus == 1 {
-        return 0;
-    }
-    let m = modulus as u128;
-    let mut result: u128 = 1;
-    let mut b: u128 = base as u128 % m;
-    while exp > 0 {
-        if exp & 1 == 1 {
-            result = result * b % m;
+/// Montgomery modular multiplication (REDC) for a fixed odd 64-bit modulus.
+///
+/// `witness` used to reduce with `result * b % m` on `u128`, and that
+/// 128-bit division dominates cost when `next_prime` probes many odd
+/// candidates for a large hash table. REDC (Montgomery 1985) replaces the
+/// division with a handful of 64-bit multiplies and a shift, at the cost of
+/// converting operands into and out of "Montgomery form" (residues scaled
+/// by R = 2^64 mod n).
+struct Montgomery {
+    n: u64,
+    /// n * neg_inv ≡ -1 (mod 2^64): the negated inverse REDC's `u` step
+    /// actually needs (`u*n` must cancel `t` mod R, i.e. `u ≡ -t*n^-1`, not
+    /// `+t*n^-1`), derived by negating the positive inverse the Newton
+    /// iteration below converges to.
+    neg_inv: u64,
+    /// 2^128 mod n, for converting a plain residue into Montgomery form.
+    r2: u64,
+}
+
+impl Montgomery {
+    /// `n` must be odd — true for every modulus `witness` ever constructs
+    /// one for, since `is_prime_det` only calls `witness` once trial
+    /// division has ruled out n being even.
+    fn new(n: u64) -> Self {
+        debug_assert!(n % 2 == 1, "Montgomery modulus must be odd");
+        // Newton's method for the inverse of n mod 2^64: ni = n is already
+        // correct mod 2^3 (n is odd), and each iteration below doubles the
+        // number of correct low bits, so 5 iterations suffice for 64 bits.
+        let mut ni: u64 = n;
+        for _ in 0..5 {
+            ni = ni.wrapping_mul(2u64.wrapping_sub(n.wrapping_mul(ni)));
         }
-        exp >>= 1;
-        b = b * b % m;
+        let neg_inv = 0u64.wrapping_sub(ni);
+        let r_mod_n = (1u128 << 64) % n as u128;
+        let r2 = (r_mod_n * r_mod_n % n as u128) as u64;
+        Montgomery { n, neg_inv, r2 }
+    }
+
+    /// REDC: (a * b * R^-1) mod n, where R = 2^64.
+    ///
+    /// `t + u * n` is guaranteed divisible by R, but — for n close to the
+    /// u64 ceiling — can itself need a 129th bit, one more than `u128`
+    /// holds. `wrapping_add` plus an explicit carry check recovers that bit
+    /// instead of silently dropping it, so this stays correct across the
+    /// full `n < 2^64` range the type promises rather than just the
+    /// practical range (well under `DELTA_MAX_TABLE`) this module actually
+    /// calls it with.
+    fn mrmul(&self, a: u64, b: u64) -> u64 {
+        let t = a as u128 * b as u128;
+        let u = (t as u64).wrapping_mul(self.neg_inv) as u128;
+        let un = u * self.n as u128;
+        let sum = t.wrapping_add(un);
+        let carry: u128 = if sum < t { 1 } else { 0 };
+        let hi = (sum >> 64) | (carry << 64);
+        let n = self.n as u128;
+        (if hi >= n { hi - n } else { hi }) as u64
+    }
+
+    /// base^exp mod n, staying in Montgomery form for every intermediate
+    /// squaring/multiply and converting in/out just once.
+    fn powmod(&self, base: u64, mut exp: u64) -> u64 {
+        let mut result = self.mrmul(1 % self.n, self.r2); // 1 in Montgomery form
+        let mut b = self.mrmul(base % self.n, self.r2);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mrmul(result, b);
+            }
+            b = self.mrmul(b, b);
+            exp >>= 1;
+        }
+        self.mrmul(result, 1) // out of Montgomery form
     }
-    result as u64
 }
 
 /// Factor n into d * 2^r, returning (d, r).
@@ -140,9 +196,10 @@ fn get_d_r(mut n: u64) -> (u64, u32) {
 /// "liar" — n may be prime.
 fn witness(a: u64, n: u64) -> bool {
     let (d, r) = get_d_r(n - 1);
-    let mut x = power_mod(a, d, n);
+    let mont = Montgomery::new(n);
+    let mut x = mont.powmod(a, d);
     for _ in 0..r {
-        let y = power_mod(x, 2, n);
+        let y = mont.powmod(x, 2);
         if y == 1 && x != 1 && x != n - 1 {
             return true;
         }
@@ -151,12 +208,67 @@ fn witness(a: u64, n: u64) -> bool {
     x != 1
 }
 
-/// Miller-Rabin probabilistic primality test with confidence `k`.
-///
-/// Pr[false positive] <= 4^{-k}.  With the default k = 100, the
-/// probability of a composite being reported as prime is < 10^{-60}.
+/// Fixed witness bases for which the Miller-Rabin `witness` loop is a
+/// *proven* primality decision (zero error probability, not merely a
+/// high-confidence bound) for every n below
+/// 3,317,044,064,679,887,385,961,981 — see Sorenson & Webster (2015),
+/// "Strong Pseudoprimes to Twelve Prime Bases". That bound comfortably
+/// covers all of `u64`, and hence every table size `next_prime` ever
+/// validates, so `is_prime_det` needs no RNG and no confidence parameter.
+const DET_WITNESS_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// First 50 primes. Used to trial-divide out obviously composite candidates
+/// — both here in `is_prime_det` and, more importantly, in `next_prime`'s
+/// search loop, where rejecting a candidate by trial division is far
+/// cheaper than paying for a Montgomery-backed modpow in `witness`.
+const SMALL_PRIMES: [u64; 50] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229,
+];
+
+/// `true` if `n` has a factor in `SMALL_PRIMES` other than `n` itself — a
+/// cheap composite witness that lets a caller skip the full `is_prime_det`
+/// probe for the large majority of candidates.
+fn has_small_prime_factor(n: u64) -> bool {
+    for &p in &SMALL_PRIMES {
+        if n == p {
+            return false;
+        }
+        if n % p == 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Deterministic primality test: trial division by `SMALL_PRIMES` followed
+/// by the Miller-Rabin `witness` loop at the fixed `DET_WITNESS_BASES`.
+/// See `DET_WITNESS_BASES` for why the latter is exact rather than
+/// probabilistic.
+pub fn is_prime_det(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    if SMALL_PRIMES.contains(&n) {
+        return true;
+    }
+    if has_small_prime_factor(n) {
+        return false;
+    }
+    for &a in &DET_WITNESS_BASES {
+        if witness(a, n) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Primality test used to auto-size hash tables (`next_prime`). Delegates to
+/// the deterministic `is_prime_det`, so results are exact and reproducible
+/// across runs, unlike the probabilistic `is_prime_mr`.
 pub fn is_prime(n: usize) -> bool {
-    is_prime_mr(n, 100)
+    is_prime_det(n as u64)
 }
 
 /// Miller-Rabin with explicit confidence parameter.
@@ -178,19 +290,229 @@ pub fn is_prime_mr(n: usize, k: u32) -> bool {
     true
 }
 
+// ── Baillie-PSW (base-2 strong probable prime + strong Lucas) ────────────
+
+fn signed_mod(a: i64, n: u64) -> u64 {
+    if a >= 0 {
+        (a as u64) % n
+    } else {
+        let r = (-a) as u64 % n;
+        if r == 0 {
+            0
+        } else {
+            n - r
+        }
+    }
+}
+
+fn mulmod(a: u64, b: u64, n: u64) -> u64 {
+    (a as u128 * b as u128 % n as u128) as u64
+}
+
+fn addmod(a: u64, b: u64, n: u64) -> u64 {
+    ((a as u128 + b as u128) % n as u128) as u64
+}
+
+fn submod(a: u64, b: u64, n: u64) -> u64 {
+    let n128 = n as u128;
+    ((a as u128 + n128 - (b as u128 % n128)) % n128) as u64
+}
+
+/// x/2 mod n, for odd n: exact when x is even, otherwise (x+n)/2 lands back
+/// on an integer since x and n are both odd.
+fn half_mod(x: u64, n: u64) -> u64 {
+    if x % 2 == 0 {
+        x / 2
+    } else {
+        ((x as u128 + n as u128) / 2) as u64
+    }
+}
+
+/// Jacobi symbol (a/n) for odd n > 0, via the standard quadratic-reciprocity
+/// reduction (binary GCD-style, no factoring required).
+fn jacobi_symbol(a: i64, n: u64) -> i32 {
+    let mut a = signed_mod(a, n);
+    let mut n = n;
+    let mut result = 1i32;
+    loop {
+        a %= n;
+        if a == 0 {
+            return if n == 1 { result } else { 0 };
+        }
+        while a % 2 == 0 {
+            a /= 2;
+            let r = n % 8;
+            if r == 3 || r == 5 {
+                result = -result;
+            }
+        }
+        if a == 1 {
+            return result;
+        }
+        if a % 4 == 3 && n % 4 == 3 {
+            result = -result;
+        }
+        std::mem::swap(&mut a, &mut n);
+    }
+}
+
+fn is_perfect_square(n: u64) -> bool {
+    let r = n.isqrt();
+    r * r == n
+}
+
+/// Strong Lucas probable-prime test under Selfridge's parameter choice: scan
+/// D over 5, -7, 9, -11, 13, ... for the first with Jacobi symbol `(D/n) =
+/// -1` (a perfect-square n never finds one, and is composite by definition),
+/// then set P = 1, Q = (1 - D)/4 and evaluate the Lucas sequences U, V at
+/// index d where n + 1 = d * 2^s via binary doubling, mirroring `witness`'s
+/// repeated-squaring shape but for the Lucas recurrences instead of modpow.
+fn strong_lucas_probable_prime(n: u64) -> bool {
+    let mut candidate_d: i64 = 5;
+    let d = loop {
+        if is_perfect_square(n) {
+            return false;
+        }
+        if jacobi_symbol(candidate_d, n) == -1 {
+            break candidate_d;
+        }
+        candidate_d = if candidate_d > 0 {
+            -(candidate_d + 2)
+        } else {
+            -candidate_d + 2
+        };
+    };
+    let q: i64 = (1 - d) / 4;
+    let d_mod = signed_mod(d, n);
+    let q_mod = signed_mod(q, n);
+
+    let (d_odd, s) = get_d_r(n.wrapping_add(1));
+    let bits = 64 - d_odd.leading_zeros();
+
+    let (mut u, mut v, mut qk) = (1u64, 1u64, q_mod);
+    for i in (0..bits - 1).rev() {
+        u = mulmod(u, v, n);
+        v = submod(mulmod(v, v, n), addmod(qk, qk, n), n);
+        qk = mulmod(qk, qk, n);
+        if (d_odd >> i) & 1 == 1 {
+            let u2 = addmod(u, v, n);
+            let v2 = addmod(mulmod(d_mod, u, n), v, n);
+            u = half_mod(u2, n);
+            v = half_mod(v2, n);
+            qk = mulmod(qk, q_mod, n);
+        }
+    }
+
+    if u == 0 || v == 0 {
+        return true;
+    }
+    for _ in 1..s {
+        v = submod(mulmod(v, v, n), addmod(qk, qk, n), n);
+        qk = mulmod(qk, qk, n);
+        if v == 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Baillie-PSW primality test: a base-2 strong probable-prime test (reusing
+/// `get_d_r` and the Montgomery-backed `witness`) combined with a strong
+/// Lucas probable-prime test (`strong_lucas_probable_prime`). No
+/// counterexample is known below 2^64, and unlike `is_prime_mr` it needs no
+/// RNG — a second guarantee story alongside `is_prime_det` for callers
+/// auto-sizing hash tables who want that property without paying for 12
+/// separate Miller-Rabin witnesses.
+pub fn is_prime_bpsw(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    if SMALL_PRIMES.contains(&n) {
+        return true;
+    }
+    if has_small_prime_factor(n) {
+        return false;
+    }
+    if witness(2, n) {
+        return false;
+    }
+    strong_lucas_probable_prime(n)
+}
+
+/// Residues mod 30 that are coprime to 2*3*5, in increasing order.
+const WHEEL_RESIDUES: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+
+/// Gaps between consecutive entries of `WHEEL_RESIDUES`, wrapping from 29
+/// back to 31 ≡ 1 (mod 30). Stepping by these instead of by 2 visits only 8
+/// of every 30 integers instead of 15 — about 1.9x fewer candidates for
+/// `next_prime` to test.
+const WHEEL_DELTAS: [u64; 8] = [6, 4, 2, 4, 2, 4, 6, 2];
+
 /// Smallest prime >= n.
 ///
-/// Searches odd candidates upward from n.  By the prime number theorem,
-/// the expected gap is O(log n), so this terminates quickly.
+/// Walks a mod-30 wheel (see `WHEEL_RESIDUES`/`WHEEL_DELTAS`) so multiples of
+/// 2, 3, and 5 are never even visited, trial-divides each surviving
+/// candidate against `SMALL_PRIMES` before paying for a full `is_prime_det`
+/// probe, and otherwise relies on the prime number theorem's O(log n)
+/// expected gap to terminate quickly.
 pub fn next_prime(n: usize) -> usize {
     if n <= 2 {
         return 2;
     }
-    let mut candidate = if n % 2 == 0 { n + 1 } else { n };
-    while !is_prime(candidate) {
-        candidate += 2;
+    if n <= 3 {
+        return 3;
+    }
+    if n <= 5 {
+        return 5;
+    }
+    let n = n as u64;
+    let base = (n / 30) * 30;
+    let (mut candidate, mut idx) = match WHEEL_RESIDUES.iter().position(|&r| base + r >= n) {
+        Some(pos) => (base + WHEEL_RESIDUES[pos], pos),
+        None => (base + 30 + WHEEL_RESIDUES[0], 0),
+    };
+    loop {
+        if !has_small_prime_factor(candidate) && is_prime_det(candidate) {
+            return candidate as usize;
+        }
+        candidate += WHEEL_DELTAS[idx];
+        idx = (idx + 1) % WHEEL_DELTAS.len();
     }
-    candidate
+}
+
+/// Every prime in `[lo, hi)`.
+///
+/// Segment-sieves the range against `SMALL_PRIMES` (marking out multiples of
+/// each, starting at `p*p` since smaller multiples already carry a smaller
+/// prime factor) and runs `is_prime_det` only on survivors — useful when a
+/// caller wants several candidate table sizes from one range instead of
+/// calling `next_prime` once per candidate. The sieve is only a prefilter,
+/// not a complete test (`SMALL_PRIMES` stops at 229, so a composite whose
+/// smallest factor exceeds that survives it), so correctness rests entirely
+/// on the `is_prime_det` confirmation, same as `next_prime`.
+pub fn primes_in_range(lo: u64, hi: u64) -> Vec<u64> {
+    if hi <= lo {
+        return Vec::new();
+    }
+    let len = (hi - lo) as usize;
+    let mut composite = vec![false; len];
+    for &p in &SMALL_PRIMES {
+        let first = std::cmp::max(p * p, lo.div_ceil(p) * p);
+        let mut m = first;
+        while m < hi {
+            composite[(m - lo) as usize] = true;
+            m += p;
+        }
+    }
+    (0..len)
+        .filter_map(|i| {
+            let candidate = lo + i as u64;
+            let is_candidate_prime = candidate >= 2
+                && !composite[i]
+                && (SMALL_PRIMES.contains(&candidate) || is_prime_det(candidate));
+            is_candidate_prime.then_some(candidate)
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -232,6 +554,37 @@ mod tests {
 
     // ── Primality testing ────────────────────────────────────────────────
 
+    #[test]
+    fn test_montgomery_powmod_matches_naive_modpow() {
+        fn naive_modpow(base: u64, mut exp: u64, modulus: u64) -> u64 {
+            let m = modulus as u128;
+            let mut result: u128 = 1;
+            let mut b: u128 = base as u128 % m;
+            while exp > 0 {
+                if exp & 1 == 1 {
+                    result = result * b % m;
+                }
+                exp >>= 1;
+                b = b * b % m;
+            }
+            result as u64
+        }
+        // 18446744073709551557 = u64::MAX - 58, a prime close to the u64 ceiling.
+        for &n in &[3u64, 7, 97, 1048573, 2097143, 18446744073709551557] {
+            let mont = Montgomery::new(n);
+            for (base, exp) in [(2u64, 10u64), (123456789, 1000), (n - 1, 17), (1, 0)] {
+                assert_eq!(
+                    mont.powmod(base, exp),
+                    naive_modpow(base, exp, n),
+                    "mismatch for base={} exp={} n={}",
+                    base,
+                    exp,
+                    n
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_get_d_r() {
         assert_eq!(get_d_r(8), (1, 3));
@@ -314,6 +667,32 @@ mod tests {
         assert_eq!(next_prime(3), 3);
     }
 
+    #[test]
+    fn test_is_prime_det_matches_is_prime_mr() {
+        for n in 0u64..2000 {
+            assert_eq!(
+                is_prime_det(n),
+                is_prime_mr(n as usize, 50),
+                "mismatch at n={}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_prime_det_large_primes() {
+        assert!(is_prime_det(1048573)); // largest prime < 2^20
+        assert!(is_prime_det(2097143)); // largest prime < 2^21
+        assert!(!is_prime_det(1048575));
+    }
+
+    #[test]
+    fn test_is_prime_det_carmichael_numbers() {
+        for &c in &[561u64, 1105, 1729, 2465, 2821, 6601, 8911] {
+            assert!(!is_prime_det(c), "Carmichael number {} should be composite", c);
+        }
+    }
+
     #[test]
     fn test_next_prime_consecutive() {
         // Verify next_prime produces valid primes for a range of inputs
@@ -323,4 +702,96 @@ mod tests {
             assert!(is_prime(np), "next_prime({}) = {} should be prime", n, np);
         }
     }
+
+    #[test]
+    fn test_next_prime_wheel_matches_brute_force() {
+        fn brute_force_next_prime(n: u64) -> u64 {
+            let mut c = n;
+            loop {
+                if is_prime_det(c) {
+                    return c;
+                }
+                c += 1;
+            }
+        }
+        for n in 0u64..3000 {
+            assert_eq!(
+                next_prime(n as usize) as u64,
+                brute_force_next_prime(n),
+                "mismatch at n = {}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_prime_wheel_skips_multiples_of_2_3_5() {
+        // Every candidate the wheel lands on past the n <= 5 special cases
+        // should be coprime to 30 before confirmation: walking
+        // `WHEEL_DELTAS` from 1 visits exactly `WHEEL_RESIDUES` (mod 30).
+        let mut c = 1u64;
+        for (i, &d) in WHEEL_DELTAS.iter().enumerate() {
+            assert_eq!(c % 30, WHEEL_RESIDUES[i]);
+            assert!(c % 2 != 0 && c % 3 != 0 && c % 5 != 0);
+            c += d;
+        }
+        assert_eq!(c % 30, WHEEL_RESIDUES[0]);
+        // ...and so is every prime `next_prime` returns.
+        for n in (100usize..10_000).step_by(37) {
+            let p = next_prime(n) as u64;
+            if p > 5 {
+                assert_ne!(p % 2, 0);
+                assert_ne!(p % 3, 0);
+                assert_ne!(p % 5, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_primes_in_range_matches_brute_force() {
+        for &(lo, hi) in &[(0u64, 100u64), (100, 300), (1_000_000, 1_000_200)] {
+            let expected: Vec<u64> = (lo..hi).filter(|&n| is_prime_det(n)).collect();
+            assert_eq!(primes_in_range(lo, hi), expected, "range [{}, {})", lo, hi);
+        }
+    }
+
+    #[test]
+    fn test_primes_in_range_empty_for_backwards_range() {
+        assert!(primes_in_range(100, 50).is_empty());
+        assert!(primes_in_range(50, 50).is_empty());
+    }
+
+    #[test]
+    fn test_is_prime_bpsw_matches_is_prime_det() {
+        for n in 0u64..5000 {
+            assert_eq!(
+                is_prime_bpsw(n),
+                is_prime_det(n),
+                "mismatch at n = {}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_prime_bpsw_large_primes() {
+        assert!(is_prime_bpsw(1048573)); // largest prime < 2^20
+        assert!(is_prime_bpsw(2097143)); // largest prime < 2^21
+        assert!(is_prime_bpsw(18446744073709551557)); // large prime near u64::MAX
+        assert!(!is_prime_bpsw(1048575));
+    }
+
+    #[test]
+    fn test_is_prime_bpsw_carmichael_numbers() {
+        for &c in &[561u64, 1105, 1729, 2465, 2821, 6601, 8911] {
+            assert!(!is_prime_bpsw(c), "Carmichael number {} should be composite", c);
+        }
+    }
+
+    #[test]
+    fn test_is_prime_bpsw_perfect_squares() {
+        for k in 2u64..100 {
+            assert!(!is_prime_bpsw(k * k), "{} is a perfect square", k * k);
+        }
+    }
 }
@@ -163,6 +163,291 @@ fn find_cycle_in_scc(
     None
 }
 
+/// The CRWI (Copy-Read/Write-Intersection) digraph `make_inplace` builds
+/// internally to schedule copies, exposed standalone for diagnosing why a
+/// delta converted copies to adds or visualizing its cycle structure.
+///
+/// Vertices are the `Copy` commands in `commands`, indexed in the order they
+/// appear (skipping `Add`/`Run`/`CopyOut`, which never take part in a CRWI
+/// edge — see `make_inplace`'s step 1 comment). `crwi_graph` performs the
+/// same derivation as `make_inplace`'s steps 1-2 and the Tarjan pass of
+/// step 3, but stops there instead of continuing to a Kahn schedule.
+#[derive(Debug)]
+pub struct CrwiGraph {
+    /// `(src, dst, length)` per vertex, indexed the same as `adj`/`condensation`.
+    pub copies: Vec<(usize, usize, usize)>,
+    /// `adj[i]` holds the `j`s such that `i` must execute before `j` (`i`'s
+    /// read interval overlaps `j`'s write interval).
+    pub adj: Vec<Vec<usize>>,
+    pub edges: usize,
+    /// Non-trivial SCCs only — the cycles a cycle-breaking policy has to
+    /// resolve. Under `Localmin` and `MinByteFvs`, a vertex not listed in
+    /// any of these is never a conversion candidate; `Constant` converts the
+    /// lowest-indexed live copy, which may lie outside them.
+    pub sccs: Vec<Vec<usize>>,
+    /// The SCC condensation (vertices grouped into components, a singleton
+    /// per acyclic copy or one entry per non-trivial SCC) in topological
+    /// order: an edge between two components only ever points from an
+    /// earlier entry to a later one.
+    pub condensation: Vec<Vec<usize>>,
+}
+
+/// Build `[adj]` for the copies described by `(src, dst, length)` triples,
+/// using the same O(n log n + E) sweep-line `make_inplace` uses: sort writes
+/// by start, then binary-search each read interval into the sorted writes to
+/// find overlaps.
+fn build_crwi_adj(copies: &[(usize, usize, usize)]) -> (Vec<Vec<usize>>, usize) {
+    let n = copies.len();
+    let mut adj: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut edges = 0usize;
+
+    let mut write_sorted: Vec<usize> = (0..n).collect();
+    write_sorted.sort_unstable_by_key(|&j| copies[j].1);
+    let write_starts: Vec<usize> = write_sorted.iter().map(|&j| copies[j].1).collect();
+
+    for i in 0..n {
+        let (si, _, li) = copies[i];
+        let read_end = si + li;
+        let lo = write_starts.partition_point(|&ws| ws < si);
+        let hi = write_starts.partition_point(|&ws| ws < read_end);
+        if lo > 0 {
+            let j = write_sorted[lo - 1];
+            if j != i {
+                let (dj, lj) = (copies[j].1, copies[j].2);
+                if dj + lj > si {
+                    adj[i].push(j);
+                    edges += 1;
+                }
+            }
+        }
+        for k in lo..hi {
+            let j = write_sorted[k];
+            if j != i {
+                adj[i].push(j);
+                edges += 1;
+            }
+        }
+    }
+
+    (adj, edges)
+}
+
+/// Derive the CRWI digraph for `commands` without scheduling it.
+///
+/// See `CrwiGraph` for the returned structure and `crwi_graph_to_dot` to
+/// render it. `make_inplace`/`make_inplace_split` build and consume an
+/// equivalent graph internally but do not return it; `make_inplace_with_graph`
+/// returns both the placed commands and the graph from a single call.
+pub fn crwi_graph(commands: &[Command]) -> CrwiGraph {
+    let mut copies: Vec<(usize, usize, usize)> = Vec::new();
+    let mut write_pos: usize = 0;
+
+    for cmd in commands {
+        match cmd {
+            Command::Copy { offset, length } => {
+                copies.push((*offset, write_pos, *length));
+                write_pos += length;
+            }
+            Command::CopyOut { length, .. } => write_pos += length,
+            Command::Add { data } => write_pos += data.len(),
+            Command::Run { length, .. } => write_pos += length,
+        }
+    }
+
+    let n = copies.len();
+    let (adj, edges) = build_crwi_adj(&copies);
+
+    // Sinks-first from Tarjan; reverse for the topological (source-first)
+    // condensation order `CrwiGraph::condensation` documents.
+    let sinks_first = tarjan_scc(&adj, n);
+    let sccs: Vec<Vec<usize>> = sinks_first.iter().filter(|scc| scc.len() > 1).cloned().collect();
+    let condensation: Vec<Vec<usize>> = sinks_first.into_iter().rev().collect();
+
+    CrwiGraph { copies, adj, edges, sccs, condensation }
+}
+
+/// Render a `CrwiGraph` as Graphviz DOT source: one node per copy (labeled
+/// with its src/dst/length), one edge per CRWI dependency, and vertices
+/// sharing a non-trivial SCC grouped into a `cluster_N` subgraph so cycles
+/// are visually obvious (e.g. via `dot -Tsvg`).
+pub fn crwi_graph_to_dot(graph: &CrwiGraph) -> String {
+    let mut scc_of: Vec<Option<usize>> = vec![None; graph.copies.len()];
+    for (id, scc) in graph.sccs.iter().enumerate() {
+        for &v in scc {
+            scc_of[v] = Some(id);
+        }
+    }
+
+    let mut out = String::from("digraph crwi {\n");
+    for (id, scc) in graph.sccs.iter().enumerate() {
+        out.push_str(&format!("  subgraph cluster_{} {{\n", id));
+        out.push_str("    style=dashed; color=red; label=\"cycle\";\n");
+        for &v in scc {
+            out.push_str(&format!("    n{};\n", v));
+        }
+        out.push_str("  }\n");
+    }
+    for (i, &(src, dst, length)) in graph.copies.iter().enumerate() {
+        if scc_of[i].is_none() {
+            out.push_str(&format!(
+                "  n{} [label=\"#{} src={} dst={} len={}\"];\n",
+                i, i, src, dst, length
+            ));
+        } else {
+            out.push_str(&format!(
+                "  n{} [label=\"#{} src={} dst={} len={}\", color=red];\n",
+                i, i, src, dst, length
+            ));
+        }
+    }
+    for (i, succs) in graph.adj.iter().enumerate() {
+        for &j in succs {
+            out.push_str(&format!("  n{} -> n{};\n", i, j));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Like `make_inplace`, but also returns the `CrwiGraph` for the same
+/// commands — for callers that want both the scheduled commands and a way
+/// to diagnose/visualize why a given copy was converted. The graph comes
+/// from a separate `crwi_graph` call, so it is derived twice: once there
+/// and once inside `make_inplace`. `make_inplace` itself stays
+/// graph-free on its return type so the common path never pays for a
+/// `CrwiGraph` it doesn't want.
+pub fn make_inplace_with_graph(
+    r: &[u8],
+    commands: &[Command],
+    policy: CyclePolicy,
+) -> (Vec<PlacedCommand>, InplaceStats, CrwiGraph) {
+    let graph = crwi_graph(commands);
+    let (placed, stats) = make_inplace(r, commands, policy);
+    (placed, stats, graph)
+}
+
+/// Pick the next victim to convert when Kahn's algorithm stalls, per
+/// `policy`. Shared by `make_inplace` and `make_inplace_split`, which differ
+/// only in what they do with the chosen victim once selected (full
+/// materialization vs. attempting a partial-copy split first).
+///
+/// `scc_active`/`color`/`scc_ptr`/`scan_pos` are caller-owned amortization
+/// state that persists across calls within the same graph (see
+/// `find_cycle_in_scc` and the `MinByteFvs` arm below for why); the caller
+/// is responsible for resetting them when the graph itself changes shape.
+#[allow(clippy::too_many_arguments)]
+fn choose_victim(
+    policy: CyclePolicy,
+    n: usize,
+    adj: &[Vec<usize>],
+    copy_info: &[(usize, usize, usize, usize)],
+    removed: &[bool],
+    in_deg: &[usize],
+    out_deg: &[usize],
+    scc_list: &[Vec<usize>],
+    scc_id: &[usize],
+    scc_active: &mut [usize],
+    color: &mut [u8],
+    scc_ptr: &mut usize,
+    scan_pos: &mut usize,
+) -> usize {
+    match policy {
+        CyclePolicy::Constant => (0..n).find(|&i| !removed[i]).unwrap(),
+        CyclePolicy::Localmin => {
+            // Advance scc_ptr past SCCs whose members are all removed.
+            // scc_active[id] == 0 means all live members were freed by
+            // Kahn or earlier conversions; this SCC needs no more work.
+            // scc_ptr advances O(|scc_list|) total across all stalls.
+            loop {
+                while *scc_ptr < scc_list.len() && scc_active[*scc_ptr] == 0 {
+                    *scc_ptr += 1;
+                    *scan_pos = 0;
+                }
+                if *scc_ptr >= scc_list.len() {
+                    // Safety fallback — should not happen with a correct graph.
+                    break (0..n).find(|&i| !removed[i]).unwrap();
+                }
+                let result = find_cycle_in_scc(
+                    adj,
+                    &scc_list[*scc_ptr],
+                    *scc_ptr,
+                    scc_id,
+                    removed,
+                    color,
+                    scan_pos,
+                );
+                match result {
+                    Some(cycle) => {
+                        break cycle
+                            .iter()
+                            .copied()
+                            .min_by_key(|&i| (copy_info[i].3, i))
+                            .unwrap();
+                    }
+                    None => {
+                        // This SCC's remaining subgraph is acyclic (all
+                        // cycles already broken); advance to next SCC.
+                        // color=2 values for this SCC's members persist
+                        // harmlessly (other SCCs use scc_id filter).
+                        *scc_ptr += 1;
+                        *scan_pos = 0;
+                    }
+                }
+            }
+        }
+        CyclePolicy::MinByteFvs => {
+            // Eades/Lin/Smyth-style degree-greedy (cf. "A fast and
+            // effective heuristic for the feedback arc set problem,"
+            // Inf. Process. Lett. 47(6):319-323, 1993), adapted from
+            // feedback-arc-set to feedback-vertex-set by weighting each
+            // candidate's removal benefit by its byte cost: among the
+            // live members of the stalled SCC, convert the vertex
+            // maximizing `(in_deg * out_deg) / length` rather than
+            // `Localmin`'s per-cycle minimum. `in_deg`/`out_deg` are
+            // already maintained incrementally by the Kahn loop above
+            // (restricted to live neighbors via `removed`), so each
+            // candidate's ratio is read off directly — no rescan of the
+            // graph, only of this SCC's member list.
+            loop {
+                while *scc_ptr < scc_list.len() && scc_active[*scc_ptr] == 0 {
+                    *scc_ptr += 1;
+                    *scan_pos = 0;
+                }
+                if *scc_ptr >= scc_list.len() {
+                    // Safety fallback — should not happen with a correct graph.
+                    break (0..n).find(|&i| !removed[i]).unwrap();
+                }
+                let best = scc_list[*scc_ptr]
+                    .iter()
+                    .copied()
+                    .filter(|&v| !removed[v])
+                    .max_by(|&a, &b| {
+                        // Compare (in_deg*out_deg)/length via cross-
+                        // multiplication (u128 headroom) to avoid float
+                        // rounding; ties break by (length, index)
+                        // ascending so output stays reproducible.
+                        let na = in_deg[a] as u128 * out_deg[a] as u128;
+                        let nb = in_deg[b] as u128 * out_deg[b] as u128;
+                        let la = copy_info[a].3 as u128;
+                        let lb = copy_info[b].3 as u128;
+                        (na * lb)
+                            .cmp(&(nb * la))
+                            .then_with(|| lb.cmp(&la))
+                            .then_with(|| b.cmp(&a))
+                    });
+                match best {
+                    Some(v) => break v,
+                    None => {
+                        // No live members left in this SCC; advance.
+                        *scc_ptr += 1;
+                        *scan_pos = 0;
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Statistics from in-place conversion.
 #[derive(Debug, Default)]
 pub struct InplaceStats {
@@ -172,6 +457,18 @@ pub struct InplaceStats {
     pub cycles_broken: usize,
     pub copies_converted: usize,
     pub bytes_converted: usize,
+    /// Bytes `CyclePolicy::Localmin` would have converted on this same CRWI
+    /// graph — equal to `bytes_converted` when plain `make_inplace` runs
+    /// with `Localmin`, otherwise (another policy, or `make_inplace_split`)
+    /// from a separate simulation (`simulate_localmin_bytes`)
+    /// run purely for comparison. Lets a caller judge whether a fancier
+    /// policy's savings are worth its extra bookkeeping.
+    pub baseline_localmin_bytes: usize,
+    /// Number of victims handled via partial-copy splitting
+    /// (`make_inplace_split`) rather than full materialization — a subset of
+    /// `copies_converted`. Always zero from plain `make_inplace`, which never
+    /// splits.
+    pub copies_split: usize,
 }
 
 /// Convert standard delta commands to in-place executable commands.
@@ -227,7 +524,19 @@ pub fn make_inplace(
     // Step 1: compute write offsets for each command
     // copy_info: (index, src, dst, length)
     let mut copy_info: Vec<(usize, usize, usize, usize)> = Vec::new();
-    let mut add_info: Vec<(usize, Vec<u8>)> = Vec::new();
+    // Add and Run never read from R, so — unlike Copy — they can never
+    // participate in a CRWI edge; both are carried through as literal
+    // placed commands in the order they were produced. CopyOut reads from
+    // the reconstructed output rather than R, so it is CRWI-irrelevant for
+    // the same reason — but unlike Add/Run its source must itself already
+    // be finalized. That holds here because literals are applied strictly
+    // after all (reordered) copies and after the Adds that replace
+    // converted copies (see step 4), in their original relative order: a
+    // CopyOut's source is always an earlier output position, so it is
+    // either inside a Copy's destination (written, as a Copy or as its
+    // replacement Add, before any literal runs) or an earlier literal in
+    // this very list (done before this one, since list order is preserved).
+    let mut literal_info: Vec<PlacedCommand> = Vec::new();
     let mut write_pos: usize = 0;
 
     for cmd in commands {
@@ -236,29 +545,44 @@ pub fn make_inplace(
                 copy_info.push((copy_info.len(), *offset, write_pos, *length));
                 write_pos += length;
             }
+            Command::CopyOut { offset, length } => {
+                literal_info.push(PlacedCommand::CopyOut {
+                    src: *offset,
+                    dst: write_pos,
+                    length: *length,
+                });
+                write_pos += length;
+            }
             Command::Add { data } => {
-                add_info.push((write_pos, data.clone()));
+                literal_info.push(PlacedCommand::Add {
+                    dst: write_pos,
+                    data: data.clone(),
+                });
                 write_pos += data.len();
             }
+            Command::Run { byte, length } => {
+                literal_info.push(PlacedCommand::Run {
+                    dst: write_pos,
+                    byte: *byte,
+                    length: *length,
+                });
+                write_pos += length;
+            }
         }
     }
 
     let n = copy_info.len();
     if n == 0 {
-        stats.num_adds = add_info.len();
-        return (
-            add_info
-                .into_iter()
-                .map(|(dst, data)| PlacedCommand::Add { dst, data })
-                .collect(),
-            stats,
-        );
+        stats.num_adds = literal_info.len();
+        return (literal_info, stats);
     }
+    let num_literals = literal_info.len();
 
     // Step 2: build CRWI digraph and global in-degree array
     // Edge i -> j means i's read interval [src_i, src_i+len_i) overlaps
     // j's write interval [dst_j, dst_j+len_j), so i must execute before j.
     let mut adj: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut radj: Vec<Vec<usize>> = vec![Vec::new(); n];
     let mut in_deg: Vec<usize> = vec![0; n];
 
     // O(n log n + E) sweep-line: sort writes by start, then for each read
@@ -285,6 +609,7 @@ pub fn make_inplace(
                 let (_, _, dj, lj) = copy_info[j];
                 if dj + lj > si {
                     adj[i].push(j);
+                    radj[j].push(i);
                     in_deg[j] += 1;
                     stats.edges += 1;
                 }
@@ -294,12 +619,19 @@ pub fn make_inplace(
             let j = write_sorted[k];
             if j != i {
                 adj[i].push(j);
+                radj[j].push(i);
                 in_deg[j] += 1;
                 stats.edges += 1;
             }
         }
     }
 
+    // Live out-degree, used only by `CyclePolicy::MinByteFvs`: starts at the
+    // full successor count and is decremented via `radj` whenever a
+    // successor is removed (by Kahn or by conversion), mirroring how
+    // `in_deg` is already maintained — never rescanned.
+    let mut out_deg: Vec<usize> = adj.iter().map(|e| e.len()).collect();
+
     // Step 3: Kahn topological sort with Tarjan-scoped cycle breaking.
     //
     // Tarjan SCC pre-decomposition identifies which vertices are in cycles
@@ -371,6 +703,11 @@ pub fn make_inplace(
                     }
                 }
             }
+            for &u in &radj[v] {
+                if !removed[u] {
+                    out_deg[u] -= 1;
+                }
+            }
         }
 
         if processed >= n {
@@ -379,54 +716,28 @@ pub fn make_inplace(
 
         // Kahn stalled: all remaining vertices are in CRWI cycles.
         // Choose a victim to convert from copy to add.
-        let victim = match policy {
-            CyclePolicy::Constant => (0..n).find(|&i| !removed[i]).unwrap(),
-            CyclePolicy::Localmin => {
-                // Advance scc_ptr past SCCs whose members are all removed.
-                // scc_active[id] == 0 means all live members were freed by
-                // Kahn or earlier conversions; this SCC needs no more work.
-                // scc_ptr advances O(|scc_list|) total across all stalls.
-                loop {
-                    while scc_ptr < scc_list.len() && scc_active[scc_ptr] == 0 {
-                        scc_ptr += 1;
-                        scan_pos = 0;
-                    }
-                    if scc_ptr >= scc_list.len() {
-                        // Safety fallback — should not happen with a correct graph.
-                        break (0..n).find(|&i| !removed[i]).unwrap();
-                    }
-                    let result = find_cycle_in_scc(
-                        &adj,
-                        &scc_list[scc_ptr],
-                        scc_ptr,
-                        &scc_id,
-                        &removed,
-                        &mut color,
-                        &mut scan_pos,
-                    );
-                    match result {
-                        Some(cycle) => {
-                            break *cycle
-                                .iter()
-                                .min_by_key(|&&i| (copy_info[i].3, i))
-                                .unwrap();
-                        }
-                        None => {
-                            // This SCC's remaining subgraph is acyclic (all
-                            // cycles already broken); advance to next SCC.
-                            // color=2 values for this SCC's members persist
-                            // harmlessly (other SCCs use scc_id filter).
-                            scc_ptr += 1;
-                            scan_pos = 0;
-                        }
-                    }
-                }
-            }
-        };
+        let victim = choose_victim(
+            policy,
+            n,
+            &adj,
+            &copy_info,
+            &removed,
+            &in_deg,
+            &out_deg,
+            &scc_list,
+            &scc_id,
+            &mut scc_active,
+            &mut color,
+            &mut scc_ptr,
+            &mut scan_pos,
+        );
 
         // Convert victim: materialize its copy data as a literal add.
         let (_, src, dst, length) = copy_info[victim];
-        add_info.push((dst, r[src..src + length].to_vec()));
+        literal_info.push(PlacedCommand::Add {
+            dst,
+            data: r[src..src + length].to_vec(),
+        });
         stats.cycles_broken += 1;
         stats.copies_converted += 1;
         stats.bytes_converted += length;
@@ -445,6 +756,11 @@ pub fn make_inplace(
                 }
             }
         }
+        for &u in &radj[victim] {
+            if !removed[u] {
+                out_deg[u] -= 1;
+            }
+        }
     }
 
     // Step 4: assemble result — copies in topo order, then all adds
@@ -457,11 +773,352 @@ pub fn make_inplace(
 
     stats.num_copies = topo_order.len();
 
-    for (dst, data) in add_info {
-        result.push(PlacedCommand::Add { dst, data });
+    // Adds of converted copies go before the original literals, so a
+    // CopyOut reading a converted copy's destination sees it written.
+    let converted = literal_info.split_off(num_literals);
+    result.extend(converted);
+    result.extend(literal_info);
+
+    stats.num_adds = result.len() - stats.num_copies;
+
+    stats.baseline_localmin_bytes = if policy == CyclePolicy::Localmin {
+        stats.bytes_converted
+    } else {
+        simulate_localmin_bytes(r, commands)
+    };
+
+    (result, stats)
+}
+
+/// Re-runs in-place conversion under `CyclePolicy::Localmin` solely to learn
+/// how many bytes it would have converted on this same CRWI graph, for
+/// `InplaceStats::baseline_localmin_bytes`. The resulting commands are
+/// discarded; only the byte count is kept.
+fn simulate_localmin_bytes(r: &[u8], commands: &[Command]) -> usize {
+    make_inplace(r, commands, CyclePolicy::Localmin)
+        .1
+        .bytes_converted
+}
+
+/// Like `make_inplace`, but tries to avoid materializing a victim's entire
+/// copy as a literal add.
+///
+/// Only the sub-range of a victim's read interval that actually overlaps an
+/// offending successor's write interval needs to be preserved from `r`
+/// before it is clobbered; the rest can remain a `Copy`. When a victim i is
+/// chosen and has a live successor j (`i`'s read interval intersects `j`'s
+/// write interval), this materializes only that overlap as an `Add` and
+/// re-inserts the surviving prefix/suffix of `i` as new `Copy` fragments —
+/// `None` if either fragment would fall below `min_fragment`, in which case
+/// the victim is fully converted exactly as `make_inplace` would.
+///
+/// New fragments can themselves be part of residual cycles not captured by
+/// the original Tarjan decomposition, so a split forces a full rebuild of
+/// the CRWI graph (edges, SCCs, Kahn heap) over the current set of live
+/// copies before continuing — "re-run the in-degree/Kahn step on the
+/// modified graph" rather than patching the existing one. A full conversion
+/// needs no rebuild, since it only removes a vertex (handled by the same
+/// incremental `in_deg`/`out_deg` decrements `make_inplace` uses) and never
+/// adds one. Splits are expected to be rare (gated by `min_fragment`), so
+/// the occasional O(n+E) rebuild is worth the simplicity.
+pub fn make_inplace_split(
+    r: &[u8],
+    commands: &[Command],
+    policy: CyclePolicy,
+    min_fragment: usize,
+) -> (Vec<PlacedCommand>, InplaceStats) {
+    let mut stats = InplaceStats::default();
+
+    if commands.is_empty() {
+        return (Vec::new(), stats);
+    }
+
+    // Step 1: same as make_inplace — separate copies (growable: splitting
+    // appends new fragments) from literals (fixed once built).
+    let mut copy_info: Vec<(usize, usize, usize, usize)> = Vec::new();
+    let mut literal_info: Vec<PlacedCommand> = Vec::new();
+    let mut write_pos: usize = 0;
+
+    for cmd in commands {
+        match cmd {
+            Command::Copy { offset, length } => {
+                copy_info.push((copy_info.len(), *offset, write_pos, *length));
+                write_pos += length;
+            }
+            Command::CopyOut { offset, length } => {
+                literal_info.push(PlacedCommand::CopyOut {
+                    src: *offset,
+                    dst: write_pos,
+                    length: *length,
+                });
+                write_pos += length;
+            }
+            Command::Add { data } => {
+                literal_info.push(PlacedCommand::Add {
+                    dst: write_pos,
+                    data: data.clone(),
+                });
+                write_pos += data.len();
+            }
+            Command::Run { byte, length } => {
+                literal_info.push(PlacedCommand::Run {
+                    dst: write_pos,
+                    byte: *byte,
+                    length: *length,
+                });
+                write_pos += length;
+            }
+        }
+    }
+
+    if copy_info.is_empty() {
+        stats.num_adds = literal_info.len();
+        return (literal_info, stats);
+    }
+    let num_literals = literal_info.len();
+
+    let mut removed: Vec<bool> = vec![false; copy_info.len()];
+    let mut topo_order: Vec<usize> = Vec::new();
+    let mut first_rebuild = true;
+
+    'rebuild: loop {
+        let n = copy_info.len();
+
+        // Step 2: CRWI digraph over the live vertices only (a later rebuild
+        // may follow a split, so some indices are already finalized).
+        let mut adj: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut radj: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut in_deg: Vec<usize> = vec![0; n];
+
+        let mut write_sorted: Vec<usize> = (0..n).filter(|&i| !removed[i]).collect();
+        write_sorted.sort_unstable_by_key(|&j| copy_info[j].2);
+        let write_starts: Vec<usize> = write_sorted.iter().map(|&j| copy_info[j].2).collect();
+
+        let mut edges = 0usize;
+        for i in 0..n {
+            if removed[i] {
+                continue;
+            }
+            let (_, si, _, li) = copy_info[i];
+            let read_end = si + li;
+            let lo = write_starts.partition_point(|&ws| ws < si);
+            let hi = write_starts.partition_point(|&ws| ws < read_end);
+            if lo > 0 {
+                let j = write_sorted[lo - 1];
+                if j != i {
+                    let (_, _, dj, lj) = copy_info[j];
+                    if dj + lj > si {
+                        adj[i].push(j);
+                        radj[j].push(i);
+                        in_deg[j] += 1;
+                        edges += 1;
+                    }
+                }
+            }
+            for k in lo..hi {
+                let j = write_sorted[k];
+                if j != i {
+                    adj[i].push(j);
+                    radj[j].push(i);
+                    in_deg[j] += 1;
+                    edges += 1;
+                }
+            }
+        }
+        if first_rebuild {
+            // Edge count of the original graph, matching what plain
+            // `make_inplace` reports — later rebuilds reflect a graph
+            // that's already had victims removed/split, not the input.
+            stats.edges = edges;
+            first_rebuild = false;
+        }
+
+        let mut out_deg: Vec<usize> = adj.iter().map(|e| e.len()).collect();
+
+        let sccs = tarjan_scc(&adj, n);
+        let mut scc_id = vec![usize::MAX; n];
+        let mut scc_list: Vec<Vec<usize>> = Vec::new();
+        let mut scc_active: Vec<usize> = Vec::new();
+        for scc in &sccs {
+            if scc.len() > 1 {
+                let id = scc_list.len();
+                for &v in scc {
+                    scc_id[v] = id;
+                }
+                scc_active.push(scc.len());
+                scc_list.push(scc.clone());
+            }
+        }
+
+        let mut color = vec![0u8; n];
+        let mut scc_ptr = 0usize;
+        let mut scan_pos = 0usize;
+
+        let mut heap: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+        for i in 0..n {
+            if !removed[i] && in_deg[i] == 0 {
+                heap.push(Reverse((copy_info[i].3, i)));
+            }
+        }
+
+        let live_count = (0..n).filter(|&i| !removed[i]).count();
+        let mut processed_this_round = 0usize;
+
+        loop {
+            while let Some(Reverse((_, v))) = heap.pop() {
+                if removed[v] {
+                    continue;
+                }
+                removed[v] = true;
+                topo_order.push(v);
+                processed_this_round += 1;
+                if scc_id[v] != usize::MAX {
+                    scc_active[scc_id[v]] -= 1;
+                }
+                for &w in &adj[v] {
+                    if !removed[w] {
+                        in_deg[w] -= 1;
+                        if in_deg[w] == 0 {
+                            heap.push(Reverse((copy_info[w].3, w)));
+                        }
+                    }
+                }
+                for &u in &radj[v] {
+                    if !removed[u] {
+                        out_deg[u] -= 1;
+                    }
+                }
+            }
+
+            if processed_this_round >= live_count {
+                break 'rebuild;
+            }
+
+            // Stalled: pick a victim via the same policy `make_inplace` uses.
+            let victim = choose_victim(
+                policy,
+                n,
+                &adj,
+                &copy_info,
+                &removed,
+                &in_deg,
+                &out_deg,
+                &scc_list,
+                &scc_id,
+                &mut scc_active,
+                &mut color,
+                &mut scc_ptr,
+                &mut scan_pos,
+            );
+
+            let (_, src, dst, length) = copy_info[victim];
+            let read_end = src + length;
+
+            // Does a live successor's write interval actually overlap the
+            // victim's read interval, and would splitting around just that
+            // overlap leave fragments long enough to bother keeping?
+            let split = adj[victim]
+                .iter()
+                .copied()
+                .find(|&j| !removed[j])
+                .and_then(|j| {
+                    let (_, _, dj, lj) = copy_info[j];
+                    let ov_start = src.max(dj);
+                    let ov_end = read_end.min(dj + lj);
+                    if ov_start >= ov_end {
+                        return None;
+                    }
+                    let prefix_len = ov_start - src;
+                    let suffix_len = read_end - ov_end;
+                    if (prefix_len == 0 && suffix_len == 0)
+                        || (prefix_len > 0 && prefix_len < min_fragment)
+                        || (suffix_len > 0 && suffix_len < min_fragment)
+                    {
+                        return None;
+                    }
+                    Some((ov_start, ov_end, prefix_len, suffix_len))
+                });
+
+            removed[victim] = true;
+            processed_this_round += 1;
+            stats.cycles_broken += 1;
+            if scc_id[victim] != usize::MAX {
+                scc_active[scc_id[victim]] -= 1;
+            }
+
+            match split {
+                Some((ov_start, ov_end, prefix_len, suffix_len)) => {
+                    // Materialize only the overlapping sub-range; the
+                    // surviving prefix/suffix re-enter the graph as copies.
+                    literal_info.push(PlacedCommand::Add {
+                        dst: dst + prefix_len,
+                        data: r[ov_start..ov_end].to_vec(),
+                    });
+                    stats.copies_converted += 1;
+                    stats.copies_split += 1;
+                    stats.bytes_converted += ov_end - ov_start;
+
+                    if prefix_len > 0 {
+                        let idx = copy_info.len();
+                        copy_info.push((idx, src, dst, prefix_len));
+                        removed.push(false);
+                    }
+                    if suffix_len > 0 {
+                        let idx = copy_info.len();
+                        copy_info.push((idx, ov_end, dst + prefix_len + (ov_end - ov_start), suffix_len));
+                        removed.push(false);
+                    }
+                    continue 'rebuild;
+                }
+                None => {
+                    // No splittable overlap (or fragments too small): fully
+                    // convert, same as make_inplace — no rebuild needed.
+                    literal_info.push(PlacedCommand::Add {
+                        dst,
+                        data: r[src..src + length].to_vec(),
+                    });
+                    stats.copies_converted += 1;
+                    stats.bytes_converted += length;
+
+                    for &w in &adj[victim] {
+                        if !removed[w] {
+                            in_deg[w] -= 1;
+                            if in_deg[w] == 0 {
+                                heap.push(Reverse((copy_info[w].3, w)));
+                            }
+                        }
+                    }
+                    for &u in &radj[victim] {
+                        if !removed[u] {
+                            out_deg[u] -= 1;
+                        }
+                    }
+                }
+            }
+        }
     }
 
+    // Step 4: assemble result — copies in topo order, then all adds
+    let mut result: Vec<PlacedCommand> = Vec::new();
+
+    for &i in &topo_order {
+        let (_, src, dst, length) = copy_info[i];
+        result.push(PlacedCommand::Copy { src, dst, length });
+    }
+
+    stats.num_copies = topo_order.len();
+
+    // Adds of converted or split copies go before the original literals,
+    // so a CopyOut reading such a copy's destination sees it written.
+    let converted = literal_info.split_off(num_literals);
+    result.extend(converted);
+    result.extend(literal_info);
+
     stats.num_adds = result.len() - stats.num_copies;
 
+    // Splitting changes what `bytes_converted` means relative to plain
+    // Localmin, so always simulate rather than short-circuiting on policy.
+    stats.baseline_localmin_bytes = simulate_localmin_bytes(r, commands);
+
     (result, stats)
 }
@@ -1,29 +1,57 @@
 pub mod types;
 pub mod hash;
+pub mod digest;
+pub mod block_index;
+pub mod varint;
 pub mod encoding;
+pub mod sink;
 pub mod splay;
 pub mod algorithm;
 pub mod apply;
 pub mod inplace;
+pub mod vcdiff;
 
 // Re-exports for convenience
 pub use types::{
-    Algorithm, Command, CyclePolicy, DeltaError, DeltaSummary, DiffOptions, PlacedCommand,
-    DELTA_ADD_HEADER, DELTA_BUF_CAP, DELTA_CMD_ADD, DELTA_CMD_COPY, DELTA_CMD_END,
-    DELTA_COPY_PAYLOAD, DELTA_FLAG_INPLACE, DELTA_HEADER_SIZE, DELTA_MAGIC, DELTA_U32_SIZE,
-    HASH_BASE, HASH_MOD, SEED_LEN, TABLE_SIZE,
+    Algorithm, ApplyError, Chunking, Command, Compressor, CyclePolicy, DeltaError,
+    DeltaSummary, DiffOptions, PlacedCommand, DELTA_ADD_HEADER, DELTA_BUF_CAP,
+    DELTA_CMD_ADD, DELTA_CMD_COPY, DELTA_CMD_COPY_OUT, DELTA_CMD_END, DELTA_CMD_RUN,
+    DELTA_COMPRESSOR_DEFLATE,
+    DELTA_COMPRESSOR_NONE, DELTA_COMPRESSOR_ZSTD, DELTA_COPY_PAYLOAD, DELTA_FLAG_INPLACE,
+    DELTA_FORMAT_COPY_OUT, DELTA_FORMAT_RUN, DELTA_FORMAT_VARINT, DELTA_HASH_LEN,
+    DELTA_HEADER_BASE_V4, DELTA_HEADER_PREFIX_V5, DELTA_HEADER_SIZE, DELTA_MAGIC, DELTA_MAGIC_V3,
+    DELTA_MAGIC_V4, DELTA_MAX_TABLE, DELTA_MIN_RUN_LENGTH, DELTA_U32_SIZE, HASH_BASE, HASH_MOD,
+    SEED_LEN, TABLE_SIZE,
 };
-pub use hash::{fingerprint, fp_to_index, is_prime, is_prime_mr, mod_mersenne, next_prime, precompute_bp, RollingHash};
-pub use encoding::{decode_delta, encode_delta, is_inplace_delta};
+pub use hash::{
+    fingerprint, fp_to_index, is_prime, is_prime_bpsw, is_prime_det, is_prime_mr, mod_mersenne,
+    next_prime, precompute_bp, primes_in_range, RollingHash,
+};
+pub use digest::{shake128_16, shake128_n};
+pub use block_index::BlockIndex;
+pub use varint::{read_varint, read_varint_usize, write_varint};
+pub use encoding::{
+    decode_delta, decode_delta_from, encode_delta, encode_delta_to, is_inplace_delta,
+    placed_summary_from, write_end, CommandReader, DeltaReader, DeltaWriter, FromReader, ToWriter,
+};
+pub use sink::{CountingSink, Sink, WriteSink};
 pub use splay::SplayTree;
 pub use algorithm::{diff, diff_default};
 pub use algorithm::greedy::{diff_greedy, diff_greedy_default};
 pub use algorithm::onepass::{diff_onepass, diff_onepass_default};
-pub use algorithm::correcting::{diff_correcting, diff_correcting_default};
+pub use algorithm::correcting::{diff_correcting, diff_correcting_default, diff_correcting_stream};
+pub use algorithm::streaming::diff_streaming;
+pub use algorithm::cdc::diff_cdc;
+pub use algorithm::signature::{diff_from_signature, signature, Signature};
 pub use apply::{
-    apply_delta, apply_delta_inplace, apply_delta_to,
-    apply_placed_inplace_to, apply_placed_to,
-    output_size, place_commands, unplace_commands,
+    apply_delta, apply_delta_checked, apply_delta_inplace, apply_delta_inplace_checked,
+    apply_delta_inplace_into, apply_delta_to, apply_placed_command_to,
+    apply_placed_inplace_command_to, apply_placed_inplace_to, apply_placed_to, output_size,
+    place_commands, unplace_commands,
+};
+pub use inplace::{
+    crwi_graph, crwi_graph_to_dot, make_inplace, make_inplace_split, make_inplace_with_graph,
+    CrwiGraph, InplaceStats,
 };
-pub use inplace::{make_inplace, InplaceStats};
 pub use types::{delta_summary, placed_summary};
+pub use vcdiff::{decode_vcdiff, encode_vcdiff};
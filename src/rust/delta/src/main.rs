@@ -6,11 +6,11 @@ use clap::{Parser, Subcommand, ValueEnum};
 use memmap2::MmapMut;
 
 use delta::{
-    Algorithm, CyclePolicy, DiffOptions,
-    apply_placed_inplace_to, apply_placed_to,
+    Algorithm, Compressor, CyclePolicy, DeltaReader, DiffOptions,
+    apply_placed_command_to, apply_placed_inplace_command_to,
     decode_delta, encode_delta,
-    make_inplace, place_commands, unplace_commands,
-    placed_summary, shake128_16,
+    make_inplace, make_inplace_split, place_commands, unplace_commands,
+    placed_summary, shake128_n,
 };
 
 /// Format a byte slice as a lowercase hex string.
@@ -78,6 +78,7 @@ impl From<AlgorithmArg> for Algorithm {
 enum PolicyArg {
     Localmin,
     Constant,
+    MinByteFvs,
 }
 
 impl From<PolicyArg> for CyclePolicy {
@@ -85,10 +86,29 @@ impl From<PolicyArg> for CyclePolicy {
         match p {
             PolicyArg::Localmin => CyclePolicy::Localmin,
             PolicyArg::Constant => CyclePolicy::Constant,
+            PolicyArg::MinByteFvs => CyclePolicy::MinByteFvs,
         }
     }
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum CompressorArg {
+    None,
+    Zstd,
+    Deflate,
+}
+
+/// `level` is only meaningful for `Zstd`/`Deflate`; `CompressorArg::None`
+/// ignores it. Takes `--compressor`/`--level` rather than implementing `From`,
+/// since the level lives on its own flag instead of inside `CompressorArg`.
+fn build_compressor(arg: CompressorArg, level: i32) -> Compressor {
+    match arg {
+        CompressorArg::None => Compressor::None,
+        CompressorArg::Zstd => Compressor::Zstd(level),
+        CompressorArg::Deflate => Compressor::Deflate(level),
+    }
+}
+
 #[derive(Parser)]
 #[command(about = "Differential compression (Ajtai et al. 2002)")]
 struct Cli {
@@ -137,6 +157,12 @@ enum Commands {
         #[arg(long, value_enum, default_value_t = PolicyArg::Localmin)]
         policy: PolicyArg,
 
+        /// Split a victim copy around just the offending overlap instead of
+        /// converting it whole, as long as both surviving fragments are at
+        /// least this many bytes (0 disables splitting)
+        #[arg(long, default_value_t = 0)]
+        min_fragment: usize,
+
         /// Print diagnostic messages to stderr
         #[arg(long)]
         verbose: bool,
@@ -144,6 +170,40 @@ enum Commands {
         /// Use splay tree instead of hash table
         #[arg(long)]
         splay: bool,
+
+        /// Use a keyed non-cryptographic block index instead of the
+        /// Karp-Rabin hash table for seed matching (greedy only)
+        #[arg(long)]
+        block_index: bool,
+
+        /// Also match against the reconstructed output itself, emitting
+        /// self-referential copies when that is cheaper than a reference
+        /// copy (greedy and correcting)
+        #[arg(long)]
+        output_copy: bool,
+
+        /// Index the reference only at non-overlapping seed-length block
+        /// boundaries instead of every position, like an rsync block
+        /// signature (onepass only); shrinks memory at the cost of only
+        /// finding block-aligned matches
+        #[arg(long)]
+        anchor_blocks: bool,
+
+        /// Secondary entropy coding applied to the Add-data section
+        #[arg(long, value_enum, default_value_t = CompressorArg::None)]
+        compressor: CompressorArg,
+
+        /// Compression level for --compressor zstd/deflate (0 = codec default)
+        #[arg(long, default_value_t = 0)]
+        level: i32,
+
+        /// Width in bytes of the SHAKE128 src/dst integrity digests (16, 32, or 64)
+        #[arg(long, default_value_t = delta::DELTA_HASH_LEN,
+              value_parser = |s: &str| s.parse::<usize>()
+                  .map_err(|e| e.to_string())
+                  .and_then(|n| if matches!(n, 16 | 32 | 64) { Ok(n) }
+                            else { Err("--digest-len must be 16, 32, or 64".to_string()) }))]
+        digest_len: usize,
     },
 
     /// Reconstruct version from delta
@@ -183,6 +243,12 @@ enum Commands {
         #[arg(long, value_enum, default_value_t = PolicyArg::Localmin)]
         policy: PolicyArg,
 
+        /// Split a victim copy around just the offending overlap instead of
+        /// converting it whole, as long as both surviving fragments are at
+        /// least this many bytes (0 disables splitting)
+        #[arg(long, default_value_t = 0)]
+        min_fragment: usize,
+
         /// Print diagnostics (cycles broken, etc.)
         #[arg(long)]
         verbose: bool,
@@ -205,8 +271,15 @@ fn main() {
             max_table,
             inplace,
             policy,
+            min_fragment,
             verbose,
             splay,
+            block_index,
+            output_copy,
+            anchor_blocks,
+            compressor,
+            level,
+            digest_len,
         } => {
             // Read files and compute SHAKE128 hashes in a single sequential
             // pass each, then use the loaded bytes for the diff algorithm.
@@ -214,14 +287,14 @@ fn main() {
                 eprintln!("Error reading {}: {}", reference, e);
                 process::exit(1);
             });
-            let src_hash = shake128_16(&r_bytes);
+            let src_hash = shake128_n(&r_bytes, digest_len);
             let r: &[u8] = &r_bytes;
 
             let v_bytes = fs::read(&version).unwrap_or_else(|e| {
                 eprintln!("Error reading {}: {}", version, e);
                 process::exit(1);
             });
-            let dst_hash = shake128_16(&v_bytes);
+            let dst_hash = shake128_n(&v_bytes, digest_len);
             let v: &[u8] = &v_bytes;
 
             let algo: Algorithm = algorithm.into();
@@ -232,6 +305,9 @@ fn main() {
                 max_table,
                 verbose,
                 use_splay: splay,
+                use_block_index: block_index,
+                use_output_copy: output_copy,
+                anchor_blocks,
                 ..DiffOptions::default()
             };
             let commands = delta::diff(algo, r, v, &opts);
@@ -239,7 +315,11 @@ fn main() {
             let pol: CyclePolicy = policy.into();
             let mut cycles_broken = 0usize;
             let placed = if inplace {
-                let (p, stats) = make_inplace(r, &commands, pol);
+                let (p, stats) = if min_fragment > 0 {
+                    make_inplace_split(r, &commands, pol, min_fragment)
+                } else {
+                    make_inplace(r, &commands, pol)
+                };
                 cycles_broken = stats.cycles_broken;
                 p
             } else {
@@ -247,7 +327,14 @@ fn main() {
             };
             let elapsed = t0.elapsed();
 
-            let delta_bytes = encode_delta(&placed, inplace, v.len(), &src_hash, &dst_hash);
+            let delta_bytes = encode_delta(
+                &placed,
+                inplace,
+                v.len(),
+                &src_hash,
+                &dst_hash,
+                build_compressor(compressor, level),
+            );
             fs::write(&delta_file, &delta_bytes).unwrap_or_else(|e| {
                 eprintln!("Error writing {}: {}", delta_file, e);
                 process::exit(1);
@@ -260,7 +347,19 @@ fn main() {
                 delta_bytes.len() as f64 / v.len() as f64
             };
             let algo_name = format!("{:?}", algo).to_lowercase();
-            let splay_tag = if splay { " [splay]" } else { "" };
+            let mut splay_tag = if block_index {
+                " [block-index]".to_string()
+            } else if splay {
+                " [splay]".to_string()
+            } else {
+                String::new()
+            };
+            if output_copy {
+                splay_tag.push_str(" [output-copy]");
+            }
+            if anchor_blocks {
+                splay_tag.push_str(" [anchor-blocks]");
+            }
             if inplace {
                 let pol_name = format!("{:?}", pol).to_lowercase();
                 println!("Algorithm:    {}{} + in-place ({})", algo_name, splay_tag, pol_name);
@@ -272,14 +371,16 @@ fn main() {
             println!("Delta:        {} ({} bytes)", delta_file, delta_bytes.len());
             println!("Compression:  {:.4} (delta/version)", ratio);
             println!(
-                "Commands:     {} copies, {} adds",
-                stats.num_copies, stats.num_adds
+                "Commands:     {} copies, {} output-copies, {} adds, {} runs",
+                stats.num_copies, stats.num_copy_outs, stats.num_adds, stats.num_runs
             );
             if inplace {
                 println!("Cycles broken: {}", cycles_broken);
             }
             println!("Copy bytes:   {}", stats.copy_bytes);
+            println!("Output-copy bytes: {}", stats.copy_out_bytes);
             println!("Add bytes:    {}", stats.add_bytes);
+            println!("Run bytes:    {}", stats.run_bytes);
             if verbose {
                 println!("Src hash:     {}", hex_str(&src_hash));
                 println!("Dst hash:     {}", hex_str(&dst_hash));
@@ -293,12 +394,12 @@ fn main() {
             output,
             ignore_hash,
         } => {
-            // Read reference and compute its hash in one sequential pass.
+            // Read reference; its hash is computed below at the digest width
+            // recorded in the delta itself.
             let r_bytes = fs::read(&reference).unwrap_or_else(|e| {
                 eprintln!("Error reading {}: {}", reference, e);
                 process::exit(1);
             });
-            let r_hash_actual = shake128_16(&r_bytes);
             let r: &[u8] = &r_bytes;
 
             let delta_bytes = fs::read(&delta_file).unwrap_or_else(|e| {
@@ -307,13 +408,20 @@ fn main() {
             });
 
             let t0 = Instant::now();
-            let (placed, is_ip, version_size, src_hash, dst_hash) =
-                decode_delta(&delta_bytes).unwrap_or_else(|e| {
-                    eprintln!("Error decoding delta: {}", e);
-                    process::exit(1);
-                });
+            // DeltaReader parses the header once and then yields commands one
+            // at a time, so they can be applied straight into the mmap'd
+            // output below without first collecting a Vec<PlacedCommand>.
+            let mut reader = DeltaReader::new(delta_bytes.as_slice()).unwrap_or_else(|e| {
+                eprintln!("Error decoding delta: {}", e);
+                process::exit(1);
+            });
+            let is_ip = reader.inplace;
+            let version_size = reader.version_size;
+            let src_hash = std::mem::take(&mut reader.src_hash);
+            let dst_hash = std::mem::take(&mut reader.dst_hash);
 
             // Pre-check: verify reference matches what was recorded at encode time.
+            let r_hash_actual = shake128_n(r, src_hash.len());
             if r_hash_actual != src_hash {
                 if !ignore_hash {
                     eprintln!(
@@ -335,7 +443,13 @@ fn main() {
                     });
                 if let Some(mut mm) = out_mmap {
                     mm[..r.len()].copy_from_slice(r);
-                    apply_placed_inplace_to(&placed, &mut mm);
+                    for cmd in reader {
+                        let cmd = cmd.unwrap_or_else(|e| {
+                            eprintln!("Error decoding delta: {}", e);
+                            process::exit(1);
+                        });
+                        apply_placed_inplace_command_to(&cmd, &mut mm);
+                    }
                     let result = mm[..version_size].to_vec();
                     mm.flush().unwrap_or_else(|e| {
                         eprintln!("Error flushing {}: {}", output, e);
@@ -357,7 +471,13 @@ fn main() {
                         process::exit(1);
                     });
                 if let Some(mut mm) = out_mmap {
-                    apply_placed_to(r, &placed, &mut mm);
+                    for cmd in reader {
+                        let cmd = cmd.unwrap_or_else(|e| {
+                            eprintln!("Error decoding delta: {}", e);
+                            process::exit(1);
+                        });
+                        apply_placed_command_to(r, &cmd, &mut mm);
+                    }
                     let result = mm.to_vec();
                     mm.flush().unwrap_or_else(|e| {
                         eprintln!("Error flushing {}: {}", output, e);
@@ -371,7 +491,7 @@ fn main() {
             let elapsed = t0.elapsed();
 
             // Post-check: verify reconstructed output matches recorded dest hash.
-            let out_hash_actual = shake128_16(&out_bytes);
+            let out_hash_actual = shake128_n(&out_bytes, dst_hash.len());
             if out_hash_actual != dst_hash {
                 if !ignore_hash {
                     eprintln!("error: output integrity check failed");
@@ -394,11 +514,22 @@ fn main() {
                 process::exit(1);
             });
 
-            let (placed, is_ip, version_size, src_hash, dst_hash) =
-                decode_delta(&delta_bytes).unwrap_or_else(|e| {
-                    eprintln!("Error decoding delta: {}", e);
-                    process::exit(1);
-                });
+            // DeltaReader (rather than decode_delta) so the stored
+            // (compressed) Add-blob size is available alongside the decoded
+            // commands, for the stored-vs-logical report below.
+            let reader = DeltaReader::new(delta_bytes.as_slice()).unwrap_or_else(|e| {
+                eprintln!("Error decoding delta: {}", e);
+                process::exit(1);
+            });
+            let is_ip = reader.inplace;
+            let version_size = reader.version_size;
+            let src_hash = reader.src_hash.clone();
+            let dst_hash = reader.dst_hash.clone();
+            let add_compressed_len = reader.add_compressed_len;
+            let placed = reader.collect::<Result<Vec<_>, _>>().unwrap_or_else(|e| {
+                eprintln!("Error decoding delta: {}", e);
+                process::exit(1);
+            });
 
             let stats = placed_summary(&placed);
             let fmt = if is_ip { "in-place" } else { "standard" };
@@ -413,8 +544,16 @@ fn main() {
                 stats.num_copies, stats.copy_bytes
             );
             println!(
-                "  Adds:       {} ({} bytes)",
-                stats.num_adds, stats.add_bytes
+                "  Output-copies: {} ({} bytes)",
+                stats.num_copy_outs, stats.copy_out_bytes
+            );
+            println!(
+                "  Adds:       {} ({} logical bytes, {} stored bytes)",
+                stats.num_adds, stats.add_bytes, add_compressed_len
+            );
+            println!(
+                "  Runs:       {} ({} bytes)",
+                stats.num_runs, stats.run_bytes
             );
             println!("Output size:  {} bytes", stats.total_output_bytes);
         }
@@ -424,6 +563,7 @@ fn main() {
             delta_in,
             delta_out,
             policy,
+            min_fragment,
             verbose,
         } => {
             // Read reference and compute hash in one sequential pass.
@@ -456,11 +596,15 @@ fn main() {
             let t0 = Instant::now();
             let pol: CyclePolicy = policy.into();
             let commands = unplace_commands(&placed);
-            let (ip_placed, ip_stats) = make_inplace(r, &commands, pol);
+            let (ip_placed, ip_stats) = if min_fragment > 0 {
+                make_inplace_split(r, &commands, pol, min_fragment)
+            } else {
+                make_inplace(r, &commands, pol)
+            };
             let elapsed = t0.elapsed();
 
             // Preserve the original src_hash and dst_hash from the input delta.
-            let ip_delta = encode_delta(&ip_placed, true, version_size, &src_hash, &dst_hash);
+            let ip_delta = encode_delta(&ip_placed, true, version_size, &src_hash, &dst_hash, Compressor::None);
             fs::write(&delta_out, &ip_delta).unwrap_or_else(|e| {
                 eprintln!("Error writing {}: {}", delta_out, e);
                 process::exit(1);
@@ -480,6 +624,18 @@ fn main() {
                         ip_stats.bytes_converted,
                     );
                 }
+                if ip_stats.copies_split > 0 {
+                    eprintln!(
+                        "  {} of those were partial splits (min-fragment={})",
+                        ip_stats.copies_split, min_fragment,
+                    );
+                }
+                if pol != CyclePolicy::Localmin {
+                    eprintln!(
+                        "  localmin baseline: {} bytes",
+                        ip_stats.baseline_localmin_bytes,
+                    );
+                }
             }
 
             let stats = placed_summary(&ip_placed);
@@ -497,11 +653,13 @@ fn main() {
             );
             println!("Format:       in-place ({})", pol_name);
             println!(
-                "Commands:     {} copies, {} adds",
-                stats.num_copies, stats.num_adds
+                "Commands:     {} copies, {} output-copies, {} adds, {} runs",
+                stats.num_copies, stats.num_copy_outs, stats.num_adds, stats.num_runs
             );
             println!("Copy bytes:   {}", stats.copy_bytes);
+            println!("Output-copy bytes: {}", stats.copy_out_bytes);
             println!("Add bytes:    {}", stats.add_bytes);
+            println!("Run bytes:    {}", stats.run_bytes);
             println!("Time:         {:.3}s", elapsed.as_secs_f64());
         }
     }
@@ -0,0 +1,89 @@
+//! Generic output sink for streaming encoders.
+//!
+//! Mirrors Mercurial's `path_encode` sink pattern: an encoder writes through
+//! a `Sink` rather than building a `Vec<u8>` directly, so the same encoding
+//! logic can target an in-memory buffer, any `std::io::Write`, or a
+//! zero-copy `CountingSink` that only tallies how many bytes would be
+//! written (used to measure the exact output length in a first pass before
+//! serializing for real in a second).
+
+use std::io::Write;
+
+pub trait Sink {
+    fn write_byte(&mut self, byte: u8);
+    fn write_bytes(&mut self, bytes: &[u8]);
+}
+
+impl Sink for Vec<u8> {
+    fn write_byte(&mut self, byte: u8) {
+        self.push(byte);
+    }
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+/// Adapts any `std::io::Write` into a `Sink`.
+///
+/// The delta format has no partial-write recovery, so a write failure here
+/// panics rather than threading a `Result` through every encoder call site;
+/// callers who need fallibility should encode into a `Vec<u8>` and write
+/// that out themselves with their own error handling.
+pub struct WriteSink<W: Write>(pub W);
+
+impl<W: Write> Sink for WriteSink<W> {
+    fn write_byte(&mut self, byte: u8) {
+        self.0.write_all(&[byte]).expect("WriteSink: write failed");
+    }
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.0.write_all(bytes).expect("WriteSink: write failed");
+    }
+}
+
+/// A zero-copy sink that only counts bytes.
+///
+/// Run an encoder once against a `CountingSink` to learn the exact output
+/// length, then again against the real sink — a reliable two-pass
+/// "measure then serialize" path without size heuristics.
+#[derive(Default)]
+pub struct CountingSink {
+    pub count: usize,
+}
+
+impl Sink for CountingSink {
+    fn write_byte(&mut self, _byte: u8) {
+        self.count += 1;
+    }
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.count += bytes.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counting_sink_matches_vec_len() {
+        let mut counted = CountingSink::default();
+        counted.write_byte(1);
+        counted.write_bytes(&[2, 3, 4]);
+
+        let mut buf = Vec::new();
+        buf.write_byte(1);
+        buf.write_bytes(&[2, 3, 4]);
+
+        assert_eq!(counted.count, buf.len());
+    }
+
+    #[test]
+    fn test_write_sink_writes_through() {
+        let mut out = Vec::new();
+        {
+            let mut sink = WriteSink(&mut out);
+            sink.write_byte(0xAB);
+            sink.write_bytes(&[1, 2, 3]);
+        }
+        assert_eq!(out, vec![0xAB, 1, 2, 3]);
+    }
+}
@@ -17,32 +17,153 @@ pub const TABLE_SIZE: usize = 1048573; // largest prime < 2^20
                                        // Section 8: correcting uses checkpointing to fit any |R|
 pub const HASH_BASE: u64 = 263;
 pub const HASH_MOD: u64 = (1 << 61) - 1; // Mersenne prime 2^61-1
-pub const DELTA_MAGIC: &[u8; 4] = b"DLT\x01";
+/// Current container magic (v5): adds a `format_version` byte after `flags`
+/// and moves `version_size`/`add_raw_len`/`add_compressed_len`/command
+/// offsets and lengths from fixed 4-byte big-endian fields to LEB128
+/// varints (see `varint`), removing v4's 4 GiB ceiling and shrinking
+/// typical deltas. `format_version` exists so a future encoding change can
+/// be dispatched on without another magic bump; `encode_delta` writes
+/// `DELTA_FORMAT_COPY_OUT` today (see below).
+pub const DELTA_MAGIC: &[u8; 4] = b"DLT\x05";
+/// v4 container magic, decode-only: a `digest_len` header byte (so
+/// `src_hash`/`dst_hash` can be any recorded width) but still fixed 4-byte
+/// big-endian `version_size`/offsets/lengths (see `encoding::decode_delta`).
+pub const DELTA_MAGIC_V4: &[u8; 4] = b"DLT\x04";
+/// v3 container magic, decode-only: same fixed-width layout as v4 but with
+/// no `digest_len` field and a fixed 16-byte src/dst digest (see
+/// `encoding::decode_delta`).
+pub const DELTA_MAGIC_V3: &[u8; 4] = b"DLT\x03";
 pub const DELTA_FLAG_INPLACE: u8 = 0x01;
 pub const DELTA_CMD_END: u8 = 0;
 pub const DELTA_CMD_COPY: u8 = 1;
 pub const DELTA_CMD_ADD: u8 = 2;
-pub const DELTA_HEADER_SIZE: usize = 9; // magic(4) + flags(1) + version_size(4)
+/// Run-length command: `length` copies of `byte` at `dst`. Cheaper than an
+/// `Add` for the long identical-byte regions (zero-fill, padding) common in
+/// version files, since its cost is fixed (opcode + two varints + one byte)
+/// instead of scaling with `length`.
+pub const DELTA_CMD_RUN: u8 = 3;
+/// Self-referential copy: `length` bytes sourced from the already
+/// reconstructed output region `[src, src+length)` — i.e. `src < dst` — at
+/// the time this command runs, rather than from the reference (see
+/// `Command::CopyOut`). Lets the diff algorithm exploit redundancy that
+/// exists within the version but not the reference (VCDIFF calls this a
+/// target-window copy).
+pub const DELTA_CMD_COPY_OUT: u8 = 4;
+/// `format_version` byte value for the varint-encoded command/length fields
+/// `encode_delta` writes into a v5 container.
+pub const DELTA_FORMAT_VARINT: u8 = 2;
+/// `format_version` byte value once `encode_delta` also emits `Run`
+/// commands. Decoders built before this format dispatch unknown opcodes to
+/// `DeltaError::InvalidFormat` (see `encoding::DeltaReader`), so this only
+/// needs to be distinguishable from `DELTA_FORMAT_VARINT`, not change how
+/// the header itself is parsed.
+pub const DELTA_FORMAT_RUN: u8 = 3;
+/// `format_version` byte value once `encode_delta` also emits `CopyOut`
+/// (target-window self-referential copies, see `DELTA_CMD_COPY_OUT`). Same
+/// reasoning as `DELTA_FORMAT_RUN`: it only needs to be distinguishable from
+/// earlier values, since a decoder that doesn't recognize the opcode already
+/// rejects it regardless of `format_version`.
+pub const DELTA_FORMAT_COPY_OUT: u8 = 4;
+/// Minimum identical-byte run length the diff layer will emit as a `Run`
+/// instead of a literal `Add` (see `algorithm::runify`). Below this, the
+/// `Run` opcode's fixed overhead (opcode + two varints + one byte) isn't
+/// worth paying relative to just storing the bytes.
+pub const DELTA_MIN_RUN_LENGTH: usize = 8;
+/// Digest width assumed for a v3 container, which predates the `digest_len`
+/// header field, and the default width used when a caller doesn't ask for
+/// a wider one.
+pub const DELTA_HASH_LEN: usize = 16;
+/// Byte codes for the `compressor` header field (Section: Add-blob coding).
+pub const DELTA_COMPRESSOR_NONE: u8 = 0;
+pub const DELTA_COMPRESSOR_ZSTD: u8 = 1;
+pub const DELTA_COMPRESSOR_DEFLATE: u8 = 2;
+// v3: magic(4) + flags(1) + compressor(1) + version_size(4) + src_hash(16)
+// + dst_hash(16) + add_raw_len(4) + add_compressed_len(4)
+pub const DELTA_HEADER_SIZE: usize =
+    4 + 1 + 1 + 4 + DELTA_HASH_LEN + DELTA_HASH_LEN + 4 + 4;
+// v4: magic(4) + flags(1) + compressor(1) + digest_len(1) + version_size(4)
+// + add_raw_len(4) + add_compressed_len(4), excluding the two digest_len-wide
+// src_hash/dst_hash fields that follow version_size.
+pub const DELTA_HEADER_BASE_V4: usize = 4 + 1 + 1 + 1 + 4 + 4 + 4;
+// v5: magic(4) + flags(1) + format_version(1) + compressor(1) + digest_len(1),
+// excluding version_size/add_raw_len/add_compressed_len (varints, parsed
+// sequentially) and the two digest_len-wide hash fields.
+pub const DELTA_HEADER_PREFIX_V5: usize = 4 + 1 + 1 + 1 + 1;
 pub const DELTA_U32_SIZE: usize = 4;
 pub const DELTA_COPY_PAYLOAD: usize = 12; // src(4) + dst(4) + len(4)
 pub const DELTA_ADD_HEADER: usize = 8; // dst(4) + len(4)
 pub const DELTA_BUF_CAP: usize = 256;
 
+/// Secondary entropy coding applied to the concatenated Add-data section of
+/// a delta container (see `encoding::encode_delta`). Copy commands and
+/// their offsets are never compressed, so in-place application is
+/// unaffected — only the literal bytes backing `Add` are coded.
+///
+/// `Zstd`/`Deflate` carry their own compression level; `0` means "use the
+/// codec's own default" rather than a literal level, since neither codec
+/// treats 0 as a meaningful setting. The level is never recorded in the
+/// container (the `compressor` header byte only distinguishes the codec, see
+/// `to_byte`/`from_byte`) because it only affects encode-time effort, not how
+/// `decode_delta` must inflate the blob back.
+///
+/// (This crate has no Cargo manifest in this tree to gate the codecs behind
+/// a `compress` feature, so they remain unconditionally compiled in, as they
+/// have been since they were introduced.)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compressor {
+    None,
+    Zstd(i32),
+    Deflate(i32),
+}
+
+impl Compressor {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Compressor::None => DELTA_COMPRESSOR_NONE,
+            Compressor::Zstd(_) => DELTA_COMPRESSOR_ZSTD,
+            Compressor::Deflate(_) => DELTA_COMPRESSOR_DEFLATE,
+        }
+    }
+
+    pub(crate) fn from_byte(b: u8) -> Result<Self, DeltaError> {
+        match b {
+            DELTA_COMPRESSOR_NONE => Ok(Compressor::None),
+            // Decoding only ever decompresses, which needs the codec but not
+            // the level it was encoded at, so the level is a don't-care here.
+            DELTA_COMPRESSOR_ZSTD => Ok(Compressor::Zstd(0)),
+            DELTA_COMPRESSOR_DEFLATE => Ok(Compressor::Deflate(0)),
+            other => Err(DeltaError::InvalidFormat(format!(
+                "unsupported Add-blob compressor code: {}",
+                other
+            ))),
+        }
+    }
+}
+
 // ============================================================================
 // Delta Commands (Section 2.1.1)
 // ============================================================================
 
-/// Algorithm output: copy from reference or add literal bytes.
+/// Algorithm output: copy from reference, copy from the output already
+/// reconstructed, add literal bytes, or repeat a single byte `length` times
+/// (see `DELTA_CMD_RUN`).
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Command {
     Copy { offset: usize, length: usize },
+    /// Self-referential copy: `offset` is a position in the output itself,
+    /// strictly before this command's destination (see `DELTA_CMD_COPY_OUT`).
+    CopyOut { offset: usize, length: usize },
     Add { data: Vec<u8> },
+    Run { byte: u8, length: usize },
 }
 
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Command::Copy { offset, length } => write!(f, "COPY(off={}, len={})", offset, length),
+            Command::CopyOut { offset, length } => {
+                write!(f, "COPY_OUT(off={}, len={})", offset, length)
+            }
             Command::Add { data } => {
                 if data.len() <= 20 {
                     write!(f, "ADD({:?})", data)
@@ -50,6 +171,7 @@ impl fmt::Display for Command {
                     write!(f, "ADD(len={})", data.len())
                 }
             }
+            Command::Run { byte, length } => write!(f, "RUN(byte={}, len={})", byte, length),
         }
     }
 }
@@ -62,11 +184,20 @@ impl fmt::Display for Command {
 ///
 /// For standard deltas, `Copy::src` is an offset into the reference and
 /// `Copy::dst` is the write position in the output.  For in-place deltas,
-/// both refer to positions in the shared working buffer.
+/// both refer to positions in the shared working buffer, and `src`/`dst` may
+/// name overlapping ranges — safe regardless of overlap direction, since
+/// application uses `copy_within` (`slice::copy_within`, a true memmove).
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PlacedCommand {
     Copy { src: usize, dst: usize, length: usize },
+    /// `src` is a position in the output buffer itself (`src < dst`); see
+    /// `Command::CopyOut`. Standard (non-in-place) application must process
+    /// these in increasing `dst` order and copy byte-by-byte when `src` and
+    /// `dst` overlap, since the source region may still be catching up to
+    /// the destination (see `apply::apply_placed_command_to`).
+    CopyOut { src: usize, dst: usize, length: usize },
     Add { dst: usize, data: Vec<u8> },
+    Run { dst: usize, byte: u8, length: usize },
 }
 
 impl fmt::Display for PlacedCommand {
@@ -75,6 +206,9 @@ impl fmt::Display for PlacedCommand {
             PlacedCommand::Copy { src, dst, length } => {
                 write!(f, "COPY(src={}, dst={}, len={})", src, dst, length)
             }
+            PlacedCommand::CopyOut { src, dst, length } => {
+                write!(f, "COPY_OUT(src={}, dst={}, len={})", src, dst, length)
+            }
             PlacedCommand::Add { dst, data } => {
                 if data.len() <= 20 {
                     write!(f, "ADD(dst={}, {:?})", dst, data)
@@ -82,6 +216,9 @@ impl fmt::Display for PlacedCommand {
                     write!(f, "ADD(dst={}, len={})", dst, data.len())
                 }
             }
+            PlacedCommand::Run { dst, byte, length } => {
+                write!(f, "RUN(dst={}, byte={}, len={})", dst, byte, length)
+            }
         }
     }
 }
@@ -101,6 +238,28 @@ pub enum Algorithm {
 pub enum CyclePolicy {
     Localmin,
     Constant,
+    /// Approximates a minimum-weight feedback vertex set over each stalled
+    /// SCC (Eades/Lin/Smyth-style degree-greedy): repeatedly converts the
+    /// live vertex maximizing `(in_deg * out_deg) / length` rather than
+    /// `Localmin`'s per-cycle minimum, which can over-convert in dense SCCs
+    /// (see `inplace::make_inplace`).
+    MinByteFvs,
+}
+
+/// Hash table ceiling used when auto-sizing from input length (Section 8.1).
+pub const DELTA_MAX_TABLE: usize = 1_073_741_827;
+
+/// Chunk-boundary strategy used by matching.
+///
+/// `Fixed` is the default: matching runs through the selected `Algorithm`'s
+/// own scan of fixed-length `p`-byte seeds, with no content-defined chunk
+/// boundaries.
+/// `Rabin` instead cuts wherever local content makes a rolling hash satisfy
+/// a mask, so edits only perturb the chunks they touch; see `algorithm::cdc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chunking {
+    Fixed,
+    Rabin { min: usize, avg: usize, max: usize },
 }
 
 /// Options for differencing algorithms.
@@ -111,6 +270,53 @@ pub struct DiffOptions {
     pub buf_cap: usize,
     pub verbose: bool,
     pub use_splay: bool,
+    /// Use `block_index::BlockIndex`'s keyed non-cryptographic mixer instead
+    /// of the Karp-Rabin `HashMap`/`SplayTree` lookup for seed matching
+    /// (`greedy` only). Candidates are still byte-verified before use, so
+    /// this only changes lookup speed, never the resulting commands.
+    pub use_block_index: bool,
+    /// Minimum copy length; raises the effective seed length when > 0.
+    pub min_copy: usize,
+    /// Also index the version bytes emitted so far and allow a `Copy` to
+    /// source from the reconstructed output instead of the reference when
+    /// that match is cheaper or longer (`greedy` and `correcting`; see
+    /// `Command::CopyOut`). `correcting` indexes output seeds under the same
+    /// checkpoint filter (and `bucket_k` bound) used for R.
+    pub use_output_copy: bool,
+    /// Ceiling on the auto-sized hash table (`--max-table`).
+    pub max_table: usize,
+    /// Chunk-boundary strategy; `Rabin` routes matching through `algorithm::cdc`
+    /// instead of the selected `Algorithm`'s seed-grid scan.
+    pub chunking: Chunking,
+    /// Index R only at non-overlapping `p`-byte block boundaries (0, p, 2p,
+    /// …) like an rsync block signature, instead of every position
+    /// (`onepass` only). Shrinks `h_r_ht` and its stored-offset count by a
+    /// factor of `p` at the cost of only finding matches anchored to those
+    /// block boundaries; V is still scanned at every position regardless.
+    pub anchor_blocks: bool,
+    /// `correcting` only: max R offsets kept per checkpoint slot. At 1
+    /// (the default) this is the original first-found policy — every later
+    /// seed landing in an occupied slot is discarded. Raising it lets a
+    /// checkpoint slot remember up to `bucket_k` offsets, so at scan time
+    /// every stored offset with a matching fingerprint is byte-verified and
+    /// extended (Step 5), and the longest extension wins — recovering the
+    /// best of several candidates instead of whichever arrived first, at
+    /// the cost of a slightly larger table and more work per checkpoint hit.
+    pub bucket_k: usize,
+    /// `correcting` only: after the main scan, re-run the matcher on every
+    /// `Add` gap against R using a halved seed length, splicing any
+    /// discovered copies back into the command stream. Checkpointing
+    /// samples R at spacing `m ≈ p`, so short matches that fall entirely
+    /// between two checkpoints are otherwise missed and land in an `Add`.
+    pub refine_gaps: bool,
+    /// Max refinement passes over leftover gaps; each pass halves the
+    /// previous pass's seed length (floor 2) and only examines `Add`s at
+    /// least that long. A pass that can no longer halve (seed length
+    /// would drop below 2) stops early regardless of this value.
+    pub refine_depth: u32,
+    /// Skip `Add` gaps shorter than this during refinement — not worth the
+    /// extra R scan for a gap too small to ever hold a useful match.
+    pub refine_min_gap: usize,
 }
 
 impl Default for DiffOptions {
@@ -121,6 +327,16 @@ impl Default for DiffOptions {
             buf_cap: DELTA_BUF_CAP,
             verbose: false,
             use_splay: false,
+            anchor_blocks: false,
+            use_block_index: false,
+            min_copy: 0,
+            use_output_copy: false,
+            max_table: DELTA_MAX_TABLE,
+            chunking: Chunking::Fixed,
+            bucket_k: 1,
+            refine_gaps: false,
+            refine_depth: 1,
+            refine_min_gap: 0,
         }
     }
 }
@@ -134,6 +350,25 @@ pub enum DeltaError {
     InvalidFormat(String),
     UnexpectedEof,
     IoError(std::io::Error),
+    /// `apply_delta_checked`/`apply_delta_inplace_checked`: the reference
+    /// does not hash to the delta's recorded `src_hash`.
+    WrongReference,
+    /// `apply_delta_checked`/`apply_delta_inplace_checked`: the reconstructed
+    /// output does not hash to the delta's recorded `dst_hash`.
+    CorruptOutput,
+    /// `apply_delta_checked`/`apply_delta_inplace_checked`: the delta is
+    /// shorter than its own header or command stream claims.
+    Truncated,
+    /// `apply_delta_checked`/`apply_delta_inplace_checked`: the delta header
+    /// is malformed, or is the wrong in-place/standard flavor for the
+    /// function called.
+    BadHeader(String),
+    /// `apply_delta_checked`/`apply_delta_inplace_checked`: a decoded
+    /// command's offset or length falls outside the reference/output it
+    /// reads from or writes to. Caught before applying, so a delta with a
+    /// forged command stream can't be used to read or write out of bounds
+    /// even when its hashes happen to check out.
+    CommandOutOfBounds,
 }
 
 impl fmt::Display for DeltaError {
@@ -142,6 +377,18 @@ impl fmt::Display for DeltaError {
             DeltaError::InvalidFormat(msg) => write!(f, "invalid delta format: {}", msg),
             DeltaError::UnexpectedEof => write!(f, "unexpected end of delta data"),
             DeltaError::IoError(e) => write!(f, "I/O error: {}", e),
+            DeltaError::WrongReference => {
+                write!(f, "reference does not match the delta's recorded source digest")
+            }
+            DeltaError::CorruptOutput => write!(
+                f,
+                "reconstructed output does not match the delta's recorded destination digest"
+            ),
+            DeltaError::Truncated => write!(f, "delta data is truncated"),
+            DeltaError::BadHeader(msg) => write!(f, "bad delta header: {}", msg),
+            DeltaError::CommandOutOfBounds => {
+                write!(f, "command offset or length out of bounds")
+            }
         }
     }
 }
@@ -154,6 +401,29 @@ impl From<std::io::Error> for DeltaError {
     }
 }
 
+/// Error from the allocation-free apply path (`apply_delta_inplace_into`),
+/// which writes into a caller-provided buffer instead of a `Vec<u8>` and so
+/// cannot recover by growing its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyError {
+    /// `out` is smaller than the reconstructed version requires.
+    OutputTooSmall { needed: usize, available: usize },
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::OutputTooSmall { needed, available } => write!(
+                f,
+                "output buffer too small: need {} bytes, have {}",
+                needed, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
 // ============================================================================
 // Summary statistics
 // ============================================================================
@@ -162,62 +432,98 @@ impl From<std::io::Error> for DeltaError {
 pub struct DeltaSummary {
     pub num_commands: usize,
     pub num_copies: usize,
+    pub num_copy_outs: usize,
     pub num_adds: usize,
+    pub num_runs: usize,
     pub copy_bytes: usize,
+    pub copy_out_bytes: usize,
     pub add_bytes: usize,
+    pub run_bytes: usize,
     pub total_output_bytes: usize,
 }
 
 pub fn delta_summary(commands: &[Command]) -> DeltaSummary {
     let mut num_copies = 0;
+    let mut num_copy_outs = 0;
     let mut num_adds = 0;
+    let mut num_runs = 0;
     let mut copy_bytes = 0;
+    let mut copy_out_bytes = 0;
     let mut add_bytes = 0;
+    let mut run_bytes = 0;
     for cmd in commands {
         match cmd {
             Command::Copy { length, .. } => {
                 num_copies += 1;
                 copy_bytes += length;
             }
+            Command::CopyOut { length, .. } => {
+                num_copy_outs += 1;
+                copy_out_bytes += length;
+            }
             Command::Add { data } => {
                 num_adds += 1;
                 add_bytes += data.len();
             }
+            Command::Run { length, .. } => {
+                num_runs += 1;
+                run_bytes += length;
+            }
         }
     }
     DeltaSummary {
         num_commands: commands.len(),
         num_copies,
+        num_copy_outs,
         num_adds,
+        num_runs,
         copy_bytes,
+        copy_out_bytes,
         add_bytes,
-        total_output_bytes: copy_bytes + add_bytes,
+        run_bytes,
+        total_output_bytes: copy_bytes + copy_out_bytes + add_bytes + run_bytes,
     }
 }
 
 pub fn placed_summary(commands: &[PlacedCommand]) -> DeltaSummary {
     let mut num_copies = 0;
+    let mut num_copy_outs = 0;
     let mut num_adds = 0;
+    let mut num_runs = 0;
     let mut copy_bytes = 0;
+    let mut copy_out_bytes = 0;
     let mut add_bytes = 0;
+    let mut run_bytes = 0;
     for cmd in commands {
         match cmd {
             PlacedCommand::Copy { length, .. } => {
                 num_copies += 1;
                 copy_bytes += length;
             }
+            PlacedCommand::CopyOut { length, .. } => {
+                num_copy_outs += 1;
+                copy_out_bytes += length;
+            }
             PlacedCommand::Add { data, .. } => {
                 num_adds += 1;
                 add_bytes += data.len();
             }
+            PlacedCommand::Run { length, .. } => {
+                num_runs += 1;
+                run_bytes += length;
+            }
         }
     }
     DeltaSummary {
         num_commands: commands.len(),
         num_copies,
+        num_copy_outs,
         num_adds,
+        num_runs,
         copy_bytes,
+        copy_out_bytes,
         add_bytes,
-        total_output_bytes: copy_bytes + add_bytes,
+        run_bytes,
+        total_output_bytes: copy_bytes + copy_out_bytes + add_bytes + run_bytes,
     }
 }
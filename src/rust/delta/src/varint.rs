@@ -0,0 +1,118 @@
+//! LEB128 unsigned varint encoding for the v5 delta container's
+//! `version_size`, `add_raw_len`/`add_compressed_len`, and per-command
+//! offsets/lengths — replacing v3/v4's fixed 4-byte big-endian fields so
+//! reference/version files aren't capped at 4 GiB and small values (the
+//! common case for copy/add lengths) cost one or two bytes instead of four.
+//!
+//! 7 data bits per byte, high bit set on every byte but the last
+//! (continuation), groups ordered least-significant-first.
+
+use crate::types::DeltaError;
+
+/// Append `value`'s LEB128 encoding to `out`.
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Decode a LEB128 varint from `data` starting at `pos`.
+///
+/// Returns the decoded value and the position just past its last byte.
+/// Rejects a varint with more continuation groups than a `u64` can hold.
+pub fn read_varint(data: &[u8], pos: usize) -> Result<(u64, usize), DeltaError> {
+    let mut value: u64 = 0;
+    let mut shift: u32 = 0;
+    let mut p = pos;
+    loop {
+        if p >= data.len() {
+            return Err(DeltaError::UnexpectedEof);
+        }
+        if shift >= 64 {
+            return Err(DeltaError::InvalidFormat("varint too large".into()));
+        }
+        let byte = data[p];
+        p += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((value, p));
+        }
+        shift += 7;
+    }
+}
+
+/// `read_varint`, converting the result to `usize` and rejecting values
+/// that wouldn't fit on this target (e.g. a value above `u32::MAX` decoded
+/// on a 32-bit build).
+pub fn read_varint_usize(data: &[u8], pos: usize) -> Result<(usize, usize), DeltaError> {
+    let (value, next) = read_varint(data, pos)?;
+    let value = usize::try_from(value).map_err(|_| {
+        DeltaError::InvalidFormat("varint does not fit in usize on this target".into())
+    })?;
+    Ok((value, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_values() {
+        for v in [0u64, 1, 127, 128, 300, 16384, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, v);
+            let (decoded, pos) = read_varint(&buf, 0).unwrap();
+            assert_eq!(decoded, v);
+            assert_eq!(pos, buf.len());
+        }
+    }
+
+    #[test]
+    fn test_small_values_use_one_byte() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 100);
+        assert_eq!(buf.len(), 1);
+    }
+
+    #[test]
+    fn test_large_value_uses_multiple_bytes() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, u64::MAX);
+        assert!(buf.len() > 1);
+    }
+
+    #[test]
+    fn test_truncated_varint_is_unexpected_eof() {
+        let buf = [0x80u8]; // continuation bit set, no following byte
+        assert!(matches!(read_varint(&buf, 0), Err(DeltaError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn test_read_varint_usize_matches_read_varint() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 65536);
+        let (v, p1) = read_varint(&buf, 0).unwrap();
+        let (u, p2) = read_varint_usize(&buf, 0).unwrap();
+        assert_eq!(u as u64, v);
+        assert_eq!(p1, p2);
+    }
+
+    #[test]
+    fn test_parses_sequential_varints_from_shared_buffer() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 1);
+        write_varint(&mut buf, 300);
+        write_varint(&mut buf, 70000);
+        let (a, p) = read_varint_usize(&buf, 0).unwrap();
+        let (b, p) = read_varint_usize(&buf, p).unwrap();
+        let (c, p) = read_varint_usize(&buf, p).unwrap();
+        assert_eq!((a, b, c), (1, 300, 70000));
+        assert_eq!(p, buf.len());
+    }
+}
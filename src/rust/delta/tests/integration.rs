@@ -1,8 +1,16 @@
+use std::io::Cursor;
+
 use delta::{
-    apply_delta, apply_delta_inplace, decode_delta, diff_correcting, diff_greedy, diff_onepass,
-    encode_delta, is_inplace_delta, is_prime, make_inplace, next_prime, output_size,
-    place_commands, shake128_16, unplace_commands, Command, CyclePolicy, DeltaError, DiffOptions,
-    PlacedCommand, TABLE_SIZE,
+    apply_delta, apply_delta_checked, apply_delta_inplace, apply_delta_inplace_checked,
+    apply_delta_inplace_into, apply_placed_to, crwi_graph, crwi_graph_to_dot,
+    decode_delta, decode_delta_from, decode_vcdiff, diff_cdc, diff_correcting,
+    diff_correcting_stream, diff_greedy,
+    diff_onepass, diff_streaming, encode_delta, encode_delta_to, encode_vcdiff, is_inplace_delta,
+    is_prime, make_inplace, make_inplace_split, make_inplace_with_graph, next_prime, output_size,
+    place_commands, placed_summary, placed_summary_from, shake128_16, shake128_n, unplace_commands,
+    write_end, ApplyError, BlockIndex, Chunking, Command, CommandReader, Compressor, CountingSink,
+    CyclePolicy, DeltaError, DeltaReader, DeltaWriter, DiffOptions, FromReader,
+    PlacedCommand, Sink, TABLE_SIZE, ToWriter, WriteSink, DELTA_FORMAT_COPY_OUT, DELTA_MIN_RUN_LENGTH,
 };
 
 // ── helpers ──────────────────────────────────────────────────────────────
@@ -16,7 +24,7 @@ fn opts(p: usize) -> DiffOptions {
 fn roundtrip(algo_fn: DiffFn, r: &[u8], v: &[u8], p: usize) -> Vec<u8> {
     let cmds = algo_fn(r, v, &opts(p));
     let placed = place_commands(&cmds);
-    let delta = encode_delta(&placed, false, output_size(&cmds), &shake128_16(r), &shake128_16(v));
+    let delta = encode_delta(&placed, false, output_size(&cmds), &shake128_16(r), &shake128_16(v), Compressor::None);
     let (placed2, _, _, sh, dh) = decode_delta(&delta).unwrap();
     assert_eq!(sh, shake128_16(r));
     assert_eq!(dh, shake128_16(v));
@@ -47,7 +55,7 @@ fn inplace_binary_roundtrip(
 ) -> Vec<u8> {
     let cmds = algo_fn(r, v, &opts(p));
     let (ip, _) = make_inplace(r, &cmds, policy);
-    let delta = encode_delta(&ip, true, v.len(), &shake128_16(r), &shake128_16(v));
+    let delta = encode_delta(&ip, true, v.len(), &shake128_16(r), &shake128_16(v), Compressor::None);
     let (ip2, _, vs, sh, dh) = decode_delta(&delta).unwrap();
     assert_eq!(sh, shake128_16(r));
     assert_eq!(dh, shake128_16(v));
@@ -66,6 +74,7 @@ fn all_policies() -> Vec<(&'static str, CyclePolicy)> {
     vec![
         ("constant", CyclePolicy::Constant),
         ("localmin", CyclePolicy::Localmin),
+        ("minbytefvs", CyclePolicy::MinByteFvs),
     ]
 }
 
@@ -167,7 +176,7 @@ fn test_binary_encoding_roundtrip() {
     ];
     let sh = [0u8; 16];
     let dh = [0xffu8; 16];
-    let encoded = encode_delta(&placed, false, 491, &sh, &dh);
+    let encoded = encode_delta(&placed, false, 491, &sh, &dh, Compressor::None);
     let (decoded, is_ip, vs, sh2, dh2) = decode_delta(&encoded).unwrap();
     assert!(!is_ip);
     assert_eq!(vs, 491);
@@ -185,8 +194,8 @@ fn test_binary_encoding_inplace_flag() {
     }];
     let sh = [1u8; 16];
     let dh = [2u8; 16];
-    let standard = encode_delta(&placed, false, 15, &sh, &dh);
-    let inplace_enc = encode_delta(&placed, true, 15, &sh, &dh);
+    let standard = encode_delta(&placed, false, 15, &sh, &dh, Compressor::None);
+    let inplace_enc = encode_delta(&placed, true, 15, &sh, &dh, Compressor::None);
 
     assert!(!is_inplace_delta(&standard));
     assert!(is_inplace_delta(&inplace_enc));
@@ -201,18 +210,399 @@ fn test_binary_encoding_inplace_flag() {
 }
 
 #[test]
-fn test_binary_encoding_magic_v2() {
-    let encoded = encode_delta(&[], false, 0, &[0u8; 16], &[0u8; 16]);
-    assert_eq!(&encoded[..4], b"DLT\x02");
+fn test_binary_encoding_magic_v5() {
+    let encoded = encode_delta(&[], false, 0, &[0u8; 16], &[0u8; 16], Compressor::None);
+    assert_eq!(&encoded[..4], b"DLT\x05");
+    assert_eq!(encoded[5], DELTA_FORMAT_COPY_OUT);
 }
 
 #[test]
 fn test_binary_encoding_wrong_magic_rejected() {
-    let mut bad = encode_delta(&[], false, 0, &[0u8; 16], &[0u8; 16]);
+    let mut bad = encode_delta(&[], false, 0, &[0u8; 16], &[0u8; 16], Compressor::None);
     bad[3] = 0x01; // downgrade to v1
     assert!(matches!(decode_delta(&bad), Err(DeltaError::InvalidFormat(_))));
 }
 
+#[test]
+fn test_binary_encoding_legacy_v3_decodes() {
+    // A v3 container predates the digest_len header byte, so absence of the
+    // field must be interpreted as a 16-byte digest.
+    let sh = [7u8; 16];
+    let dh = [9u8; 16];
+    let placed = vec![PlacedCommand::Add { dst: 0, data: b"hi".to_vec() }];
+    let mut cmd_stream = vec![2u8, 0, 0, 0, 0, 0, 0, 0, 2, 0];
+    let mut v3 = Vec::new();
+    v3.extend_from_slice(b"DLT\x03");
+    v3.push(0); // flags: not in-place
+    v3.push(0); // compressor: none
+    v3.extend_from_slice(&5u32.to_be_bytes()); // version_size
+    v3.extend_from_slice(&sh);
+    v3.extend_from_slice(&dh);
+    v3.extend_from_slice(&2u32.to_be_bytes()); // add_raw_len
+    v3.extend_from_slice(&2u32.to_be_bytes()); // add_compressed_len
+    v3.append(&mut cmd_stream);
+    v3.extend_from_slice(b"hi");
+
+    let (decoded, is_ip, version_size, sh2, dh2) = decode_delta(&v3).unwrap();
+    assert!(!is_ip);
+    assert_eq!(version_size, 5);
+    assert_eq!(sh2, sh);
+    assert_eq!(dh2, dh);
+    assert_eq!(decoded, placed);
+}
+
+#[test]
+fn test_binary_encoding_wide_digest_roundtrip() {
+    let placed = vec![PlacedCommand::Copy { src: 0, dst: 0, length: 4 }];
+    let sh = shake128_n(b"reference", 32);
+    let dh = shake128_n(b"version", 32);
+    let encoded = encode_delta(&placed, false, 4, &sh, &dh, Compressor::None);
+    let (decoded, _, _, sh2, dh2) = decode_delta(&encoded).unwrap();
+    assert_eq!(decoded, placed);
+    assert_eq!(sh2, sh);
+    assert_eq!(dh2, dh);
+    assert_eq!(sh2.len(), 32);
+}
+
+// ── hash-verified checked-apply APIs ─────────────────────────────────────
+
+#[test]
+fn test_apply_delta_checked_roundtrip() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let placed = place_commands(&cmds);
+    let delta = encode_delta(
+        &placed,
+        false,
+        output_size(&cmds),
+        &shake128_16(r),
+        &shake128_16(v),
+        Compressor::None,
+    );
+    let out = apply_delta_checked(r, &delta).unwrap();
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_apply_delta_checked_wrong_reference_rejected() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let placed = place_commands(&cmds);
+    let delta = encode_delta(
+        &placed,
+        false,
+        output_size(&cmds),
+        &shake128_16(r),
+        &shake128_16(v),
+        Compressor::None,
+    );
+    let wrong_r = b"totally different reference";
+    assert!(matches!(
+        apply_delta_checked(wrong_r, &delta),
+        Err(DeltaError::WrongReference)
+    ));
+}
+
+#[test]
+fn test_apply_delta_checked_corrupt_output_rejected() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let placed = place_commands(&cmds);
+    // Record a dst_hash that doesn't match what the commands actually produce.
+    let delta = encode_delta(
+        &placed,
+        false,
+        output_size(&cmds),
+        &shake128_16(r),
+        &shake128_16(b"not the real output"),
+        Compressor::None,
+    );
+    assert!(matches!(
+        apply_delta_checked(r, &delta),
+        Err(DeltaError::CorruptOutput)
+    ));
+}
+
+#[test]
+fn test_apply_delta_checked_truncated_rejected() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let placed = place_commands(&cmds);
+    let delta = encode_delta(
+        &placed,
+        false,
+        output_size(&cmds),
+        &shake128_16(r),
+        &shake128_16(v),
+        Compressor::None,
+    );
+    let truncated = &delta[..delta.len() - 1];
+    assert!(matches!(
+        apply_delta_checked(r, truncated),
+        Err(DeltaError::Truncated) | Err(DeltaError::BadHeader(_))
+    ));
+}
+
+#[test]
+fn test_apply_delta_checked_rejects_inplace_delta() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let (ip, _) = make_inplace(r, &cmds, CyclePolicy::Localmin);
+    let delta = encode_delta(&ip, true, v.len(), &shake128_16(r), &shake128_16(v), Compressor::None);
+    assert!(matches!(
+        apply_delta_checked(r, &delta),
+        Err(DeltaError::BadHeader(_))
+    ));
+}
+
+#[test]
+fn test_apply_delta_checked_rejects_out_of_bounds_copy() {
+    let r = b"the quick brown fox";
+    // A forged command stream: src_hash matches r, but the Copy reads far
+    // past the end of it. An attacker who knows r can always make src_hash
+    // check out, so this must be caught independently of the digests.
+    let placed = vec![PlacedCommand::Copy {
+        src: 1_000_000,
+        dst: 0,
+        length: 5,
+    }];
+    let delta = encode_delta(&placed, false, 5, &shake128_16(r), &shake128_16(b"xxxxx"), Compressor::None);
+    assert!(matches!(
+        apply_delta_checked(r, &delta),
+        Err(DeltaError::CommandOutOfBounds)
+    ));
+}
+
+#[test]
+fn test_apply_delta_inplace_checked_rejects_out_of_bounds_copy() {
+    let r = b"the quick brown fox";
+    let placed = vec![PlacedCommand::Copy {
+        src: 1_000_000,
+        dst: 0,
+        length: 5,
+    }];
+    let delta = encode_delta(&placed, true, 5, &shake128_16(r), &shake128_16(b"xxxxx"), Compressor::None);
+    assert!(matches!(
+        apply_delta_inplace_checked(r, &delta),
+        Err(DeltaError::CommandOutOfBounds)
+    ));
+}
+
+#[test]
+fn test_apply_delta_inplace_checked_roundtrip() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let (ip, _) = make_inplace(r, &cmds, CyclePolicy::Localmin);
+    let delta = encode_delta(&ip, true, v.len(), &shake128_16(r), &shake128_16(v), Compressor::None);
+    let out = apply_delta_inplace_checked(r, &delta).unwrap();
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_apply_delta_inplace_checked_rejects_standard_delta() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let placed = place_commands(&cmds);
+    let delta = encode_delta(
+        &placed,
+        false,
+        output_size(&cmds),
+        &shake128_16(r),
+        &shake128_16(v),
+        Compressor::None,
+    );
+    assert!(matches!(
+        apply_delta_inplace_checked(r, &delta),
+        Err(DeltaError::BadHeader(_))
+    ));
+}
+
+// ── allocation-free in-place apply ───────────────────────────────────────
+
+#[test]
+fn test_apply_delta_inplace_into_roundtrip() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let (ip, _) = make_inplace(r, &cmds, CyclePolicy::Localmin);
+
+    let mut out = [0u8; 64];
+    let written = apply_delta_inplace_into(r, &ip, v.len(), &mut out).unwrap();
+    assert_eq!(written, v.len());
+    assert_eq!(&out[..written], v);
+}
+
+#[test]
+fn test_apply_delta_inplace_into_matches_apply_delta_inplace() {
+    let r = b"abcdefghij".repeat(20);
+    let v = b"abcXefghijYYYghij".repeat(20);
+    let cmds = diff_greedy(&r, &v, &opts(4));
+    let (ip, _) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
+
+    let expected = apply_delta_inplace(&r, &ip, v.len());
+    let mut out = vec![0u8; r.len().max(v.len())];
+    let written = apply_delta_inplace_into(&r, &ip, v.len(), &mut out).unwrap();
+    assert_eq!(&out[..written], &expected[..]);
+}
+
+#[test]
+fn test_apply_delta_inplace_into_output_too_small() {
+    let r = b"the quick brown fox";
+    let v = b"the quick red fox jumps";
+    let cmds = diff_greedy(r, v, &opts(2));
+    let (ip, _) = make_inplace(r, &cmds, CyclePolicy::Localmin);
+
+    let mut out = [0u8; 4]; // smaller than r.len().max(v.len())
+    let err = apply_delta_inplace_into(r, &ip, v.len(), &mut out).unwrap_err();
+    assert_eq!(
+        err,
+        ApplyError::OutputTooSmall {
+            needed: r.len().max(v.len()),
+            available: 4,
+        }
+    );
+}
+
+// ── secondary entropy coding of the Add-data blob ───────────────────────
+
+#[test]
+fn test_binary_encoding_zstd_roundtrip() {
+    let data: Vec<u8> = b"the quick brown fox jumps over the lazy dog ".repeat(40);
+    let placed = vec![PlacedCommand::Add {
+        dst: 0,
+        data: data.clone(),
+    }];
+    let sh = [3u8; 16];
+    let dh = [4u8; 16];
+    let encoded = encode_delta(&placed, false, data.len(), &sh, &dh, Compressor::Zstd(0));
+    let plain = encode_delta(&placed, false, data.len(), &sh, &dh, Compressor::None);
+    assert!(encoded.len() < plain.len());
+
+    let (decoded, _, vs, _, _) = decode_delta(&encoded).unwrap();
+    assert_eq!(vs, data.len());
+    assert_eq!(decoded, placed);
+}
+
+#[test]
+fn test_binary_encoding_deflate_roundtrip() {
+    let data: Vec<u8> = b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".repeat(20);
+    let placed = vec![
+        PlacedCommand::Add {
+            dst: 0,
+            data: data.clone(),
+        },
+        PlacedCommand::Copy {
+            src: 0,
+            dst: data.len(),
+            length: 10,
+        },
+    ];
+    let sh = [5u8; 16];
+    let dh = [6u8; 16];
+    let encoded = encode_delta(&placed, false, data.len() + 10, &sh, &dh, Compressor::Deflate(0));
+    let (decoded, _, vs, sh2, dh2) = decode_delta(&encoded).unwrap();
+    assert_eq!(vs, data.len() + 10);
+    assert_eq!(sh2, sh);
+    assert_eq!(dh2, dh);
+    assert_eq!(decoded, placed);
+}
+
+#[test]
+fn test_binary_encoding_unknown_compressor_rejected() {
+    let mut encoded = encode_delta(&[], false, 0, &[0u8; 16], &[0u8; 16], Compressor::None);
+    encoded[6] = 0xff; // compressor byte follows magic (4) + flags (1) + format_version (1)
+    assert!(matches!(decode_delta(&encoded), Err(DeltaError::InvalidFormat(_))));
+}
+
+// ── Sink-based streaming encoder ────────────────────────────────────────
+
+#[test]
+fn test_encode_delta_to_matches_encode_delta() {
+    let placed = vec![
+        PlacedCommand::Add {
+            dst: 0,
+            data: vec![9, 8, 7],
+        },
+        PlacedCommand::Copy {
+            src: 40,
+            dst: 3,
+            length: 12,
+        },
+    ];
+    let sh = [7u8; 16];
+    let dh = [8u8; 16];
+
+    let via_vec = encode_delta(&placed, false, 15, &sh, &dh, Compressor::None);
+
+    let mut via_sink = Vec::new();
+    encode_delta_to(&mut via_sink, &placed, false, 15, &sh, &dh, Compressor::None);
+
+    assert_eq!(via_vec, via_sink);
+}
+
+#[test]
+fn test_counting_sink_predicts_exact_output_length() {
+    let placed = vec![PlacedCommand::Add {
+        dst: 0,
+        data: b"some add payload bytes".to_vec(),
+    }];
+    let sh = [1u8; 16];
+    let dh = [2u8; 16];
+
+    let mut counter = CountingSink::default();
+    encode_delta_to(&mut counter, &placed, false, 23, &sh, &dh, Compressor::Zstd(0));
+
+    let encoded = encode_delta(&placed, false, 23, &sh, &dh, Compressor::Zstd(0));
+    assert_eq!(counter.count, encoded.len());
+}
+
+#[test]
+fn test_write_sink_round_trip() {
+    let placed = vec![PlacedCommand::Copy {
+        src: 0,
+        dst: 0,
+        length: 5,
+    }];
+    let sh = [3u8; 16];
+    let dh = [4u8; 16];
+
+    let mut buf = Vec::new();
+    {
+        let mut sink = WriteSink(&mut buf);
+        encode_delta_to(&mut sink, &placed, false, 5, &sh, &dh, Compressor::None);
+    }
+
+    let (decoded, _, vs, _, _) = decode_delta(&buf).unwrap();
+    assert_eq!(vs, 5);
+    assert_eq!(decoded, placed);
+}
+
+#[test]
+fn test_decode_delta_from_reader() {
+    let placed = vec![PlacedCommand::Add {
+        dst: 0,
+        data: vec![1, 2, 3, 4, 5],
+    }];
+    let sh = [5u8; 16];
+    let dh = [6u8; 16];
+    let encoded = encode_delta(&placed, false, 5, &sh, &dh, Compressor::Deflate(0));
+
+    let mut cursor = Cursor::new(encoded);
+    let (decoded, is_ip, vs, sh2, dh2) = decode_delta_from(&mut cursor).unwrap();
+    assert!(!is_ip);
+    assert_eq!(vs, 5);
+    assert_eq!(sh2, sh);
+    assert_eq!(dh2, dh);
+    assert_eq!(decoded, placed);
+}
+
 // TestLargeCopy
 #[test]
 fn test_large_copy_roundtrip() {
@@ -223,11 +613,11 @@ fn test_large_copy_roundtrip() {
     }];
     let sh = [3u8; 16];
     let dh = [4u8; 16];
-    let encoded = encode_delta(&placed, false, 50000, &sh, &dh);
+    let encoded = encode_delta(&placed, false, 50000, &sh, &dh, Compressor::None);
     let (decoded, _, _, _, _) = decode_delta(&encoded).unwrap();
     assert_eq!(decoded.len(), 1);
     match &decoded[0] {
-        PlacedCommand::Copy { src, dst, length } => {
+        PlacedCommand::Copy { src, dst, length, .. } => {
             assert_eq!(*src, 100000);
             assert_eq!(*dst, 0);
             assert_eq!(*length, 50000);
@@ -246,7 +636,7 @@ fn test_large_add_roundtrip() {
     }];
     let sh = [5u8; 16];
     let dh = [6u8; 16];
-    let encoded = encode_delta(&placed, false, big_data.len(), &sh, &dh);
+    let encoded = encode_delta(&placed, false, big_data.len(), &sh, &dh, Compressor::None);
     let (decoded, _, _, _, _) = decode_delta(&encoded).unwrap();
     assert_eq!(decoded.len(), 1);
     match &decoded[0] {
@@ -497,7 +887,7 @@ fn test_standard_not_detected_as_inplace() {
         .collect();
     let cmds = diff_greedy(&r, &v, &opts(2));
     let placed = place_commands(&cmds);
-    let delta = encode_delta(&placed, false, v.len(), &shake128_16(&r), &shake128_16(&v));
+    let delta = encode_delta(&placed, false, v.len(), &shake128_16(&r), &shake128_16(&v), Compressor::None);
     assert!(!is_inplace_delta(&delta));
 }
 
@@ -517,7 +907,7 @@ fn test_inplace_detected() {
         .collect();
     let cmds = diff_greedy(&r, &v, &opts(2));
     let (ip, _) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
-    let delta = encode_delta(&ip, true, v.len(), &shake128_16(&r), &shake128_16(&v));
+    let delta = encode_delta(&ip, true, v.len(), &shake128_16(&r), &shake128_16(&v), Compressor::None);
     assert!(is_inplace_delta(&delta));
 }
 
@@ -783,6 +1173,198 @@ fn test_localmin_picks_smallest() {
     );
 }
 
+// TestMinByteFvsBaselineStat
+#[test]
+fn test_minbytefvs_baseline_stat() {
+    let blocks = make_blocks();
+    let r = blocks_ref(&blocks);
+    let v: Vec<u8> = blocks.iter().rev().flat_map(|b| b.iter().copied()).collect();
+
+    let cmds = diff_greedy(&r, &v, &opts(4));
+
+    let (_, lmin_stats) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
+    assert_eq!(lmin_stats.baseline_localmin_bytes, lmin_stats.bytes_converted);
+
+    let (_, fvs_stats) = make_inplace(&r, &cmds, CyclePolicy::MinByteFvs);
+    assert_eq!(fvs_stats.baseline_localmin_bytes, lmin_stats.bytes_converted);
+    assert!(
+        fvs_stats.bytes_converted <= fvs_stats.baseline_localmin_bytes,
+        "minbytefvs ({}) should be <= localmin baseline ({})",
+        fvs_stats.bytes_converted,
+        fvs_stats.baseline_localmin_bytes
+    );
+}
+
+// ── partial-copy splitting ────────────────────────────────────────────────
+
+// TestMakeInplaceSplitRoundtrip
+#[test]
+fn test_make_inplace_split_roundtrip() {
+    let blocks = make_blocks();
+    let r = blocks_ref(&blocks);
+    let v: Vec<u8> = blocks.iter().rev().flat_map(|b| b.iter().copied()).collect();
+
+    for (algo_name, algo) in all_algos() {
+        for (pol_name, pol) in all_policies() {
+            let cmds = algo(&r, &v, &opts(4));
+            for min_fragment in [1usize, 8, 64] {
+                let (ip, _) = make_inplace_split(&r, &cmds, pol, min_fragment);
+                let out = apply_delta_inplace(&r, &ip, v.len());
+                assert_eq!(
+                    out, v,
+                    "failed for {}/{} with min_fragment={}",
+                    algo_name, pol_name, min_fragment
+                );
+            }
+        }
+    }
+}
+
+// TestMakeInplaceSplitReducesBytesConverted
+#[test]
+fn test_make_inplace_split_reduces_bytes_converted() {
+    let blocks = make_blocks();
+    let r = blocks_ref(&blocks);
+    let v: Vec<u8> = blocks.iter().rev().flat_map(|b| b.iter().copied()).collect();
+    let cmds = diff_greedy(&r, &v, &opts(4));
+
+    let (_, full_stats) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
+    let (_, split_stats) = make_inplace_split(&r, &cmds, CyclePolicy::Localmin, 1);
+
+    assert!(split_stats.copies_split > 0, "expected at least one split");
+    assert!(
+        split_stats.bytes_converted <= full_stats.bytes_converted,
+        "split ({}) should be <= full conversion ({})",
+        split_stats.bytes_converted,
+        full_stats.bytes_converted
+    );
+}
+
+// TestMakeInplaceSplitFallsBackBelowMinFragment
+#[test]
+fn test_make_inplace_split_falls_back_below_min_fragment() {
+    let blocks = make_blocks();
+    let r = blocks_ref(&blocks);
+    let v: Vec<u8> = blocks.iter().rev().flat_map(|b| b.iter().copied()).collect();
+    let cmds = diff_greedy(&r, &v, &opts(4));
+
+    let (_, full_stats) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
+    // A fragment floor larger than any copy can never be split, so this must
+    // behave exactly like full conversion.
+    let (_, split_stats) = make_inplace_split(&r, &cmds, CyclePolicy::Localmin, usize::MAX);
+
+    assert_eq!(split_stats.copies_split, 0);
+    assert_eq!(split_stats.bytes_converted, full_stats.bytes_converted);
+}
+
+// ── self-overlapping copies (memmove direction) ──────────────────────────
+
+// TestMakeInplaceSelfOverlapBackward
+#[test]
+fn test_make_inplace_self_overlap_backward() {
+    // Add writes [0, 5); the lone Copy then reads [0, 10) into [5, 15) —
+    // src < dst < src+length, the case that corrupts under ascending order.
+    let r = b"0123456789ABCDEF".to_vec();
+    let cmds = vec![
+        Command::Add { data: vec![0xAA; 5] },
+        Command::Copy { offset: 0, length: 10 },
+    ];
+    let (placed, stats) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
+    assert_eq!(stats.cycles_broken, 0, "a single copy can't cycle with itself");
+    assert!(placed.iter().any(|c| matches!(c, PlacedCommand::Copy { .. })));
+
+    // apply_placed_inplace_command_to's Copy arm always uses copy_within, so
+    // this overlap direction is exercised purely by the bytes coming out right.
+    let out = apply_delta_inplace(&r, &placed, 15);
+    let mut expected = vec![0xAAu8; 5];
+    expected.extend_from_slice(&r[0..10]);
+    assert_eq!(out, expected);
+}
+
+// TestMakeInplaceSelfOverlapForward
+#[test]
+fn test_make_inplace_self_overlap_forward() {
+    // The lone Copy reads [5, 15) into [0, 10) — dst < src < dst+length,
+    // safe ascending.
+    let r = b"0123456789ABCDEF".to_vec();
+    let cmds = vec![Command::Copy { offset: 5, length: 10 }];
+    let (placed, _) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
+    assert!(matches!(placed[0], PlacedCommand::Copy { .. }));
+
+    let out = apply_delta_inplace(&r, &placed, 10);
+    assert_eq!(out, &r[5..15]);
+}
+
+// ── CRWI graph export ─────────────────────────────────────────────────────
+
+// TestCrwiGraphAcyclicHasNoSccs
+#[test]
+fn test_crwi_graph_acyclic_has_no_sccs() {
+    // Copy 0 writes [0,5); Copy 1 reads [0,5) (from R) into [5,10) — Copy 1's
+    // read interval overlaps Copy 0's write interval, so Copy 1 must execute
+    // first: an edge 1 -> 0, no cycle.
+    let cmds = vec![
+        Command::Copy { offset: 100, length: 5 },
+        Command::Copy { offset: 0, length: 5 },
+    ];
+    let graph = crwi_graph(&cmds);
+    assert_eq!(graph.copies, vec![(100, 0, 5), (0, 5, 5)]);
+    assert_eq!(graph.adj, vec![vec![], vec![0]]);
+    assert_eq!(graph.edges, 1);
+    assert!(graph.sccs.is_empty());
+    assert_eq!(graph.condensation, vec![vec![1], vec![0]]);
+}
+
+// TestCrwiGraphCycleIsOneScc
+#[test]
+fn test_crwi_graph_cycle_is_one_scc() {
+    // Two copies whose read/write intervals overlap each other: 0 reads
+    // what 1 writes and 1 reads what 0 writes, so 0<->1 is a 2-cycle.
+    let cmds = vec![
+        Command::Copy { offset: 5, length: 5 },
+        Command::Copy { offset: 0, length: 5 },
+    ];
+    let graph = crwi_graph(&cmds);
+    assert_eq!(graph.adj, vec![vec![1], vec![0]]);
+    assert_eq!(graph.sccs.len(), 1);
+    let mut scc = graph.sccs[0].clone();
+    scc.sort_unstable();
+    assert_eq!(scc, vec![0, 1]);
+    assert_eq!(graph.condensation.len(), 1);
+}
+
+// TestCrwiGraphToDotMentionsEachCopyAndCycle
+#[test]
+fn test_crwi_graph_to_dot_mentions_each_copy_and_cycle() {
+    let cmds = vec![
+        Command::Copy { offset: 5, length: 5 },
+        Command::Copy { offset: 0, length: 5 },
+    ];
+    let graph = crwi_graph(&cmds);
+    let dot = crwi_graph_to_dot(&graph);
+    assert!(dot.starts_with("digraph crwi {"));
+    assert!(dot.contains("n0"));
+    assert!(dot.contains("n1"));
+    assert!(dot.contains("n0 -> n1"));
+    assert!(dot.contains("n1 -> n0"));
+    assert!(dot.contains("cluster_0"));
+}
+
+// TestMakeInplaceWithGraphMatchesMakeInplace
+#[test]
+fn test_make_inplace_with_graph_matches_make_inplace() {
+    let r = b"0123456789ABCDEF".to_vec();
+    let cmds = vec![
+        Command::Copy { offset: 5, length: 5 },
+        Command::Copy { offset: 0, length: 5 },
+    ];
+    let (placed, stats) = make_inplace(&r, &cmds, CyclePolicy::Localmin);
+    let (placed_wg, stats_wg, graph) = make_inplace_with_graph(&r, &cmds, CyclePolicy::Localmin);
+    assert_eq!(placed, placed_wg);
+    assert_eq!(stats.cycles_broken, stats_wg.cycles_broken);
+    assert_eq!(graph.sccs.len(), 1);
+}
+
 // ── checkpointing: correcting with various table sizes ──────────────────
 
 #[test]
@@ -811,42 +1393,337 @@ fn test_correcting_checkpointing_various_sizes() {
     }
 }
 
+// ── correcting: bucketed checkpoint slots (bucket_k) ─────────────────────
+
 #[test]
-fn test_next_prime_is_prime() {
-    // Verify that next_prime always returns a prime, and that the TABLE_SIZE
-    // constant is itself prime.
-    assert!(is_prime(TABLE_SIZE), "TABLE_SIZE should be prime");
-    assert!(is_prime(next_prime(1048574)));
-    assert_eq!(next_prime(1048573), 1048573);
+fn test_correcting_bucket_k_picks_longer_extension() {
+    // R has two occurrences of the same 24-byte seed; only the second one's
+    // continuation matches V. bucket_k=1 (first-found) can only ever see
+    // the first occurrence, so it finds a bare p-length copy followed by an
+    // Add for the rest. bucket_k=2 keeps both offsets and, at scan time,
+    // extends each and picks the one with the longest match.
+    let seed: Vec<u8> = (0..24).map(|i| b'A' + (i % 26) as u8).collect();
+    let bad_tail: Vec<u8> = vec![b'X'; 24];
+    let good_tail: Vec<u8> = (0..24).map(|i| b'a' + (i % 26) as u8).collect();
+
+    let mut r = seed.clone();
+    r.extend_from_slice(&bad_tail);
+    r.extend_from_slice(&seed);
+    r.extend_from_slice(&good_tail);
+
+    let mut v = seed.clone();
+    v.extend_from_slice(&good_tail);
+
+    let opts_k1 = DiffOptions { p: 24, q: 2, bucket_k: 1, ..DiffOptions::default() };
+    let cmds_k1 = diff_correcting(&r, &v, &opts_k1);
+    assert_eq!(apply_delta(&r, &cmds_k1), v);
+    let copy_bytes_k1: usize = cmds_k1
+        .iter()
+        .map(|c| match c {
+            Command::Copy { length, .. } => *length,
+            _ => 0,
+        })
+        .sum();
+    assert!(copy_bytes_k1 < v.len(), "bucket_k=1 should only find the short first-occurrence copy");
+
+    let opts_k2 = DiffOptions { p: 24, q: 2, bucket_k: 2, ..DiffOptions::default() };
+    let cmds_k2 = diff_correcting(&r, &v, &opts_k2);
+    assert_eq!(apply_delta(&r, &cmds_k2), v);
+    assert_eq!(
+        cmds_k2,
+        vec![Command::Copy { offset: 48, length: v.len() }],
+        "bucket_k=2 should find the single copy covering all of v"
+    );
 }
 
-// ── inplace subcommand path ───────────────────────────────────────────────
-//
-// The `delta inplace` subcommand converts a standard delta to inplace format
-// without re-encoding from source: decode → unplace → make_inplace → encode.
-// These tests verify that path is equivalent to the direct encode --inplace path.
+#[test]
+fn test_correcting_bucket_k_splay_picks_longer_extension() {
+    // Same idea routed through the splay-tree lookup path: R's two seed
+    // occurrences share one fingerprint, so they land in the same splay
+    // bucket. bucket_k=1 keeps only the first (bad) offset and falls back
+    // to two Copy/Add pairs; bucket_k=2 keeps both and picks the longer
+    // extension, producing a single copy.
+    let seed = b"badcbadcbadc".to_vec();
+    let bad_tail = b"cbadcbadcbad".to_vec();
+    let good_tail = b"dcbadcbadcbadcbadcbadcba".to_vec();
+
+    let mut r = seed.clone();
+    r.extend_from_slice(&bad_tail);
+    r.extend_from_slice(&seed);
+    r.extend_from_slice(&good_tail);
+
+    let mut v = seed.clone();
+    v.extend_from_slice(&good_tail);
+
+    let opts_k1 = DiffOptions { p: 12, q: 2, use_splay: true, bucket_k: 1, ..DiffOptions::default() };
+    let cmds_k1 = diff_correcting(&r, &v, &opts_k1);
+    assert_eq!(apply_delta(&r, &cmds_k1), v);
+    assert_ne!(
+        cmds_k1,
+        vec![Command::Copy { offset: 24, length: v.len() }],
+        "bucket_k=1 should not find the single best copy"
+    );
 
-/// Simulate the `delta inplace` subcommand: encode a standard delta, then
-/// convert it via decode → unplace_commands → make_inplace → encode(inplace).
-fn via_inplace_subcommand(
-    algo_fn: DiffFn,
-    r: &[u8],
-    v: &[u8],
-    policy: CyclePolicy,
-    p: usize,
-) -> Vec<u8> {
-    // Step 1: encode a standard delta (compute hashes in same pass as data)
-    let cmds = algo_fn(r, v, &opts(p));
-    let placed = place_commands(&cmds);
+    let opts_k2 = DiffOptions { p: 12, q: 2, use_splay: true, bucket_k: 2, ..DiffOptions::default() };
+    let cmds_k2 = diff_correcting(&r, &v, &opts_k2);
+    assert_eq!(apply_delta(&r, &cmds_k2), v);
+    assert_eq!(cmds_k2, vec![Command::Copy { offset: 24, length: v.len() }]);
+}
+
+#[test]
+fn test_correcting_bucket_k_default_is_first_found() {
+    // bucket_k defaults to 1, matching the original first-found behavior.
+    assert_eq!(DiffOptions::default().bucket_k, 1);
+}
+
+// ── correcting: output-copy (self-referential CopyOut) ───────────────────
+
+#[test]
+fn test_correcting_output_copy_disabled_by_default() {
+    assert!(!DiffOptions::default().use_output_copy);
+}
+
+#[test]
+fn test_correcting_output_copy_finds_internal_repetition() {
+    // R shares nothing with V; V is six repeats of one phrase. With
+    // use_output_copy off, every repeat after the first is indistinguishable
+    // from R and lands in one big Add. With it on, the first repeat is
+    // still an Add (nothing to source it from yet), but every later repeat
+    // is pulled from the reconstructed output via a single CopyOut.
+    let r = b"completely unrelated reference data that shares nothing".to_vec();
+    let pattern = b"the quick brown fox jumps over the lazy dog. ".to_vec();
+    let mut v = Vec::new();
+    for _ in 0..6 {
+        v.extend_from_slice(&pattern);
+    }
+
+    let opts_off = DiffOptions { p: 8, use_output_copy: false, ..DiffOptions::default() };
+    let cmds_off = diff_correcting(&r, &v, &opts_off);
+    assert_eq!(apply_delta(&r, &cmds_off), v);
+    assert!(!cmds_off.iter().any(|c| matches!(c, Command::CopyOut { .. })));
+
+    let opts_on = DiffOptions { p: 8, use_output_copy: true, ..DiffOptions::default() };
+    let cmds_on = diff_correcting(&r, &v, &opts_on);
+    assert_eq!(apply_delta(&r, &cmds_on), v);
+    assert_eq!(
+        cmds_on,
+        vec![
+            Command::Add { data: pattern.clone() },
+            Command::CopyOut { offset: 0, length: pattern.len() * 5 },
+        ]
+    );
+}
+
+#[test]
+fn test_correcting_output_copy_roundtrips_with_splay_and_bucket_k() {
+    // Same scenario as above, routed through every lookup-table combination
+    // this flag can be paired with.
+    let r = b"xyzw".to_vec();
+    let pattern = b"abcdabcdabcd".to_vec();
+    let mut v = Vec::new();
+    for _ in 0..5 {
+        v.extend_from_slice(&pattern);
+    }
+
+    for use_splay in [false, true] {
+        for bucket_k in [1usize, 2, 4] {
+            let opts = DiffOptions {
+                p: 4,
+                q: 2,
+                use_splay,
+                bucket_k,
+                use_output_copy: true,
+                ..DiffOptions::default()
+            };
+            let cmds = diff_correcting(&r, &v, &opts);
+            assert_eq!(
+                apply_delta(&r, &cmds),
+                v,
+                "use_splay={} bucket_k={}",
+                use_splay,
+                bucket_k
+            );
+        }
+    }
+}
+
+// ── correcting: recursive gap refinement (refine_gaps) ───────────────────
+
+#[test]
+fn test_correcting_refine_gaps_disabled_by_default() {
+    let defaults = DiffOptions::default();
+    assert!(!defaults.refine_gaps);
+    assert_eq!(defaults.refine_depth, 1);
+    assert_eq!(defaults.refine_min_gap, 0);
+}
+
+#[test]
+fn test_correcting_refine_gaps_finds_match_missed_by_main_scan() {
+    // r and v share only a 5-byte run ("QRSTU"); everything else is drawn
+    // from disjoint alphabets so no 8-byte seed can ever match. With p=8
+    // the main scan can't find this run at all — it's shorter than the
+    // seed itself — so it should land entirely in Add/Run commands.
+    // min_copy=4 makes a 5-byte match long enough to be worth keeping once
+    // refine_gaps (seed length 4) is able to locate it.
+    let mut r = vec![b'A'; 8];
+    r.extend_from_slice(b"QRSTU");
+    r.extend(vec![b'B'; 8]);
+
+    let mut v = vec![b'1'; 8];
+    v.extend(vec![b'2'; 8]);
+    v.extend_from_slice(b"QRSTU");
+    v.extend(vec![b'3'; 8]);
+
+    let opts_off = DiffOptions { p: 8, min_copy: 4, refine_gaps: false, ..DiffOptions::default() };
+    let cmds_off = diff_correcting(&r, &v, &opts_off);
+    assert_eq!(apply_delta(&r, &cmds_off), v);
+    assert!(
+        !cmds_off
+            .iter()
+            .any(|c| matches!(c, Command::Copy { .. } | Command::CopyOut { .. })),
+        "a 5-byte match can't be found by an 8-byte seed, refine_gaps off or not"
+    );
+
+    let opts_on = DiffOptions { p: 8, min_copy: 4, refine_gaps: true, ..DiffOptions::default() };
+    let cmds_on = diff_correcting(&r, &v, &opts_on);
+    assert_eq!(apply_delta(&r, &cmds_on), v);
+    let copy_bytes: usize = cmds_on
+        .iter()
+        .map(|c| match c {
+            Command::Copy { length, .. } => *length,
+            _ => 0,
+        })
+        .sum();
+    assert!(
+        copy_bytes >= 5,
+        "refine_gaps should recover the QRSTU run as a Copy, got commands {:?}",
+        cmds_on
+    );
+    assert!(cmds_on.iter().any(|c| matches!(c, Command::Copy { offset: 8, .. })));
+}
+
+#[test]
+fn test_correcting_refine_gaps_respects_min_gap() {
+    // Same scenario as above, but refine_min_gap is set above the Add's
+    // length, so the gap is skipped entirely and nothing is reclaimed.
+    let mut r = vec![b'A'; 8];
+    r.extend_from_slice(b"QRSTU");
+    r.extend(vec![b'B'; 8]);
+
+    let mut v = vec![b'1'; 8];
+    v.extend(vec![b'2'; 8]);
+    v.extend_from_slice(b"QRSTU");
+    v.extend(vec![b'3'; 8]);
+
+    let opts = DiffOptions {
+        p: 8,
+        min_copy: 4,
+        refine_gaps: true,
+        refine_min_gap: v.len() + 1,
+        ..DiffOptions::default()
+    };
+    let cmds = diff_correcting(&r, &v, &opts);
+    assert_eq!(apply_delta(&r, &cmds), v);
+    assert!(!cmds.iter().any(|c| matches!(c, Command::Copy { .. } | Command::CopyOut { .. })));
+}
+
+#[test]
+fn test_correcting_refine_gaps_depth_zero_is_noop() {
+    let mut r = vec![b'A'; 8];
+    r.extend_from_slice(b"QRSTU");
+    r.extend(vec![b'B'; 8]);
+
+    let mut v = vec![b'1'; 8];
+    v.extend(vec![b'2'; 8]);
+    v.extend_from_slice(b"QRSTU");
+    v.extend(vec![b'3'; 8]);
+
+    let opts = DiffOptions {
+        p: 8,
+        min_copy: 4,
+        refine_gaps: true,
+        refine_depth: 0,
+        ..DiffOptions::default()
+    };
+    let cmds = diff_correcting(&r, &v, &opts);
+    assert_eq!(apply_delta(&r, &cmds), v);
+    assert!(!cmds.iter().any(|c| matches!(c, Command::Copy { .. } | Command::CopyOut { .. })));
+}
+
+#[test]
+fn test_correcting_refine_gaps_rebases_output_copy_offset() {
+    // Regression test: a refined gap that isn't at output offset 0 used to
+    // splice in a CopyOut whose offset was relative to the gap's own local
+    // slice instead of the whole reconstructed output, corrupting the bytes
+    // on apply without any error.
+    //
+    // One 8-byte anchor shared by r and v splits v into a pre-anchor Add and
+    // a post-anchor Add; p=8 finds the anchor as a Copy but is too coarse to
+    // see anything inside either Add. The post-anchor Add is "ABCDEABCDE",
+    // an internal repeat only findable by use_output_copy once refine_gaps
+    // drops the seed length to p'=4 — and it starts at output offset 16, not
+    // 0, which is what exposes the bad-offset bug.
+    let anchor = b"ANCHOR01";
+    let mut r = anchor.to_vec();
+    r.extend(vec![b'x'; 18]);
+
+    let mut v = vec![b'P'; 8];
+    v.extend_from_slice(anchor);
+    v.extend_from_slice(b"ABCDEABCDE");
+
+    let opts = DiffOptions {
+        p: 8,
+        min_copy: 4,
+        use_output_copy: true,
+        refine_gaps: true,
+        refine_depth: 2,
+        refine_min_gap: 8,
+        ..DiffOptions::default()
+    };
+    let cmds = diff_correcting(&r, &v, &opts);
+    assert_eq!(apply_delta(&r, &cmds), v);
+    assert!(
+        cmds.iter().any(|c| matches!(c, Command::CopyOut { .. })),
+        "expected the refined post-anchor gap to use CopyOut, got {cmds:?}"
+    );
+}
+
+#[test]
+fn test_next_prime_is_prime() {
+    // Verify that next_prime always returns a prime, and that the TABLE_SIZE
+    // constant is itself prime.
+    assert!(is_prime(TABLE_SIZE), "TABLE_SIZE should be prime");
+    assert!(is_prime(next_prime(1048574)));
+    assert_eq!(next_prime(1048573), 1048573);
+}
+
+// ── inplace subcommand path ───────────────────────────────────────────────
+//
+// The `delta inplace` subcommand converts a standard delta to inplace format
+// without re-encoding from source: decode → unplace → make_inplace → encode.
+// These tests verify that path is equivalent to the direct encode --inplace path.
+
+/// Simulate the `delta inplace` subcommand: encode a standard delta, then
+/// convert it via decode → unplace_commands → make_inplace → encode(inplace).
+fn via_inplace_subcommand(
+    algo_fn: DiffFn,
+    r: &[u8],
+    v: &[u8],
+    policy: CyclePolicy,
+    p: usize,
+) -> Vec<u8> {
+    // Step 1: encode a standard delta (compute hashes in same pass as data)
+    let cmds = algo_fn(r, v, &opts(p));
+    let placed = place_commands(&cmds);
     let sh = shake128_16(r);
     let dh = shake128_16(v);
-    let standard = encode_delta(&placed, false, v.len(), &sh, &dh);
+    let standard = encode_delta(&placed, false, v.len(), &sh, &dh, Compressor::None);
     // Step 2: decode it back, unplace, convert to inplace; preserve hashes
     let (placed2, is_ip, version_size, src_hash, dst_hash) = decode_delta(&standard).unwrap();
     assert!(!is_ip, "standard delta should not be flagged as inplace");
     let cmds2 = unplace_commands(&placed2);
     let (ip, _) = make_inplace(r, &cmds2, policy);
-    encode_delta(&ip, true, version_size, &src_hash, &dst_hash)
+    encode_delta(&ip, true, version_size, &src_hash, &dst_hash, Compressor::None)
 }
 
 #[test]
@@ -887,7 +1764,7 @@ fn test_inplace_subcommand_idempotent() {
             let (ip, _) = make_inplace(r, &cmds, pol);
             let sh = shake128_16(r);
             let dh = shake128_16(v);
-            let ip_delta = encode_delta(&ip, true, v.len(), &sh, &dh);
+            let ip_delta = encode_delta(&ip, true, v.len(), &sh, &dh, Compressor::None);
 
             // Feeding the inplace delta to the subcommand logic should detect
             // is_ip=true and return the bytes unchanged.
@@ -918,7 +1795,7 @@ fn test_inplace_subcommand_equiv_direct() {
                 // Direct path
                 let cmds = algo_fn(r, v, &opts(2));
                 let (ip_direct, _) = make_inplace(r, &cmds, pol);
-                let direct_bytes = encode_delta(&ip_direct, true, v.len(), &sh, &dh);
+                let direct_bytes = encode_delta(&ip_direct, true, v.len(), &sh, &dh, Compressor::None);
 
                 // Subcommand path
                 let subcommand_bytes = via_inplace_subcommand(algo_fn, r, v, pol, 2);
@@ -930,6 +1807,261 @@ fn test_inplace_subcommand_equiv_direct() {
     }
 }
 
+// ── VCDIFF interop ───────────────────────────────────────────────────────
+
+#[test]
+fn test_vcdiff_roundtrip_all_algos() {
+    let r: Vec<u8> = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+        .iter()
+        .cycle()
+        .take(26 * 100)
+        .copied()
+        .collect();
+    let v: Vec<u8> = b"0123EFGHIJKLMNOPQRS456ABCDEFGHIJKL789"
+        .iter()
+        .cycle()
+        .take(37 * 100)
+        .copied()
+        .collect();
+    for (name, algo) in all_algos() {
+        let cmds = algo(&r, &v, &opts(4));
+        let placed = place_commands(&cmds);
+        let encoded = encode_vcdiff(&placed, r.len());
+        let decoded = decode_vcdiff(&encoded).unwrap();
+        let mut out = vec![0u8; v.len()];
+        apply_placed_to(&r, &decoded, &mut out);
+        assert_eq!(out, v, "failed for {}", name);
+    }
+}
+
+#[test]
+fn test_vcdiff_empty_reference() {
+    let v = b"hello world".to_vec();
+    let cmds = diff_greedy(b"", &v, &opts(2));
+    let placed = place_commands(&cmds);
+    let encoded = encode_vcdiff(&placed, 0);
+    let decoded = decode_vcdiff(&encoded).unwrap();
+    let mut out = vec![0u8; v.len()];
+    apply_placed_to(b"", &decoded, &mut out);
+    assert_eq!(out, v);
+}
+
+// ── content-defined chunking ─────────────────────────────────────────────
+
+#[test]
+fn test_cdc_scattered_insertions_full_pipeline() {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(42);
+    let r: Vec<u8> = (0..2000).map(|_| rng.gen()).collect();
+    let mut v = r.clone();
+    // Single-byte insertions (rather than substitutions, as in
+    // `test_scattered_modifications`) are exactly what shifts a fixed
+    // p-byte grid but should barely disturb content-defined boundaries.
+    for _ in 0..100 {
+        let idx = rng.gen_range(0..v.len());
+        v.insert(idx, rng.gen());
+    }
+    let opts = DiffOptions {
+        chunking: Chunking::Rabin { min: 8, avg: 32, max: 128 },
+        ..opts(4)
+    };
+    let cmds = diff_cdc(&r, &v, &opts);
+    let placed = place_commands(&cmds);
+    let delta = encode_delta(&placed, false, output_size(&cmds), &shake128_16(&r), &shake128_16(&v), Compressor::None);
+    let (placed2, _, _, sh, dh) = decode_delta(&delta).unwrap();
+    assert_eq!(sh, shake128_16(&r));
+    assert_eq!(dh, shake128_16(&v));
+    let mut out = vec![0u8; v.len()];
+    apply_placed_to(&r, &placed2, &mut out);
+    assert_eq!(out, v);
+}
+
+// ── onepass: block-anchored index ────────────────────────────────────────
+
+#[test]
+fn test_onepass_anchor_blocks_roundtrip() {
+    let r: Vec<u8> = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+        .iter()
+        .cycle()
+        .take(26 * 50)
+        .copied()
+        .collect();
+    let mut v = r.clone();
+    // Edits confined to whole blocks keep the surviving blocks aligned to
+    // their original p-byte boundaries, which is exactly what anchor_blocks
+    // requires to still find them.
+    v.truncate(8);
+    v.extend_from_slice(b"xxxxxxxx");
+    v.extend_from_slice(&r[16..]);
+    let o = DiffOptions { anchor_blocks: true, ..opts(8) };
+    let cmds = diff_onepass(&r, &v, &o);
+    assert_eq!(apply_delta(&r, &cmds), v);
+}
+
+#[test]
+fn test_onepass_anchor_blocks_tail_copy_starts_on_block_boundary() {
+    // A single inserted byte shifts every later block boundary in V by one,
+    // so with anchoring on, onepass can still only match R at *its* fixed
+    // boundaries — the tail copy must start from one of those, unlike the
+    // unanchored table which indexes every R position.
+    let r: Vec<u8> = b"0123456789"
+        .iter()
+        .cycle()
+        .take(10 * 40)
+        .copied()
+        .collect();
+    let mut v = Vec::with_capacity(r.len() + 1);
+    v.push(b'!');
+    v.extend_from_slice(&r);
+    let o = DiffOptions { anchor_blocks: true, ..opts(10) };
+    let cmds = diff_onepass(&r, &v, &o);
+    assert_eq!(apply_delta(&r, &cmds), v, "anchored onepass must still round-trip");
+    assert!(cmds
+        .iter()
+        .all(|c| !matches!(c, Command::Copy { offset, .. } if offset % 10 != 0)));
+}
+
+#[test]
+fn test_onepass_anchor_blocks_identical_input_is_all_copy() {
+    // Same |R| either way; anchoring should index it roughly 1/p as densely,
+    // which is what actually buys back the memory the request asks for.
+    let r: Vec<u8> = (0u8..=255).cycle().take(2000).collect();
+    let v = r.clone();
+    let plain = diff_onepass(&r, &v, &opts(8));
+    let anchored = diff_onepass(&r, &v, &DiffOptions { anchor_blocks: true, ..opts(8) });
+    // Both still round-trip identically...
+    assert_eq!(apply_delta(&r, &plain), v);
+    assert_eq!(apply_delta(&r, &anchored), v);
+    // ...and an all-Copy identical-input diff is the simplest way to see
+    // that anchoring didn't silently break matching for the common case.
+    assert!(anchored.iter().all(|c| matches!(c, Command::Copy { .. })));
+}
+
+// ── streaming (windowed) diff ───────────────────────────────────────────
+
+#[test]
+fn test_diff_streaming_roundtrip_small_window() {
+    let r: Vec<u8> = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+        .iter()
+        .cycle()
+        .take(26 * 50)
+        .copied()
+        .collect();
+    let v: Vec<u8> = b"0123EFGHIJKLMNOPQRS456ABCDEFGHIJKL789"
+        .iter()
+        .cycle()
+        .take(37 * 50)
+        .copied()
+        .collect();
+    // A window much smaller than either input forces repeated eviction.
+    let cmds: Vec<Command> =
+        diff_streaming(Cursor::new(r.clone()), Cursor::new(v.clone()), &opts(4), 64).collect();
+    let out = apply_delta(&r, &cmds);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_diff_streaming_matches_in_memory_window_covers_all() {
+    let r = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let v = b"the quick red fox jumps over the lazy cat".to_vec();
+    // A window that comfortably covers both inputs should behave like an
+    // ordinary in-memory diff: a faithful round trip either way.
+    let streamed: Vec<Command> =
+        diff_streaming(Cursor::new(r.clone()), Cursor::new(v.clone()), &opts(3), 4096).collect();
+    let out = apply_delta(&r, &streamed);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_diff_streaming_empty_inputs() {
+    let cmds: Vec<Command> =
+        diff_streaming(Cursor::new(Vec::new()), Cursor::new(Vec::new()), &opts(4), 64).collect();
+    assert!(cmds.is_empty());
+}
+
+#[test]
+fn test_diff_streaming_empty_reference() {
+    let v = b"hello streaming world, this has no matching reference".to_vec();
+    let cmds: Vec<Command> =
+        diff_streaming(Cursor::new(Vec::new()), Cursor::new(v.clone()), &opts(4), 32).collect();
+    let out = apply_delta(&[], &cmds);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_diff_streaming_through_encode_delta() {
+    let r: Vec<u8> = (0u8..=255).cycle().take(2000).collect();
+    let mut v = r.clone();
+    v.truncate(1500);
+    v.extend_from_slice(b"appended tail that shares no seeds with r");
+    let cmds: Vec<Command> =
+        diff_streaming(Cursor::new(r.clone()), Cursor::new(v.clone()), &opts(4), 128).collect();
+    let placed = place_commands(&cmds);
+    let src_hash = shake128_16(&r);
+    let dst_hash = shake128_16(&v);
+    let encoded = encode_delta(&placed, false, v.len(), &src_hash, &dst_hash, Compressor::None);
+    let (decoded, inplace, version_size, dec_src_hash, dec_dst_hash) =
+        decode_delta(&encoded).unwrap();
+    assert!(!inplace);
+    assert_eq!(version_size, v.len());
+    assert_eq!(dec_src_hash, src_hash);
+    assert_eq!(dec_dst_hash, dst_hash);
+    let mut out = vec![0u8; version_size];
+    apply_placed_to(&r, &decoded, &mut out);
+    assert_eq!(out, v);
+}
+
+// ── correcting, streamed V scan ─────────────────────────────────────────
+
+#[test]
+fn test_diff_correcting_stream_roundtrip_small_window() {
+    let r: Vec<u8> = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+        .iter()
+        .cycle()
+        .take(26 * 50)
+        .copied()
+        .collect();
+    let v: Vec<u8> = b"0123EFGHIJKLMNOPQRSTUVWXYZ456ABCDEFGHIJKL789"
+        .iter()
+        .cycle()
+        .take(44 * 50)
+        .copied()
+        .collect();
+    // A window much smaller than V forces repeated eviction of the buffered
+    // tail, the same scenario that once overflowed on subtraction.
+    let mut cmds = Vec::new();
+    diff_correcting_stream(&r, Cursor::new(v.clone()), &opts(4), 64, |c| cmds.push(c));
+    let out = apply_delta(&r, &cmds);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_diff_correcting_stream_matches_in_memory_window_covers_all() {
+    let r = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let v = b"the quick red fox jumps over the lazy cat".to_vec();
+    let mut streamed = Vec::new();
+    diff_correcting_stream(&r, Cursor::new(v.clone()), &opts(3), 4096, |c| streamed.push(c));
+    let out = apply_delta(&r, &streamed);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_diff_correcting_stream_empty_inputs() {
+    let mut cmds = Vec::new();
+    diff_correcting_stream(&[], Cursor::new(Vec::new()), &opts(4), 64, |c| cmds.push(c));
+    assert!(cmds.is_empty());
+}
+
+#[test]
+fn test_diff_correcting_stream_empty_reference() {
+    let v = b"hello streamed world, this has no matching reference".to_vec();
+    let mut cmds = Vec::new();
+    diff_correcting_stream(&[], Cursor::new(v.clone()), &opts(4), 32, |c| cmds.push(c));
+    let out = apply_delta(&[], &cmds);
+    assert_eq!(out, v);
+}
+
 // ── shake128_16 tests ─────────────────────────────────────────────────────
 
 #[test]
@@ -968,6 +2100,8 @@ fn test_shake128_16_nist_one_byte_bd() {
     assert_eq!(shake128_16(b"\xbd"), expected);
 }
 
+// ── shake128_16 tests ─────────────────────────────────────────────────────
+
 #[test]
 fn test_shake128_16_nist_200_byte_a3() {
     // NIST FIPS 202 SHAKE128 test vector: msg = 0xa3 * 200, first 16 bytes
@@ -977,3 +2111,368 @@ fn test_shake128_16_nist_200_byte_a3() {
     ];
     assert_eq!(shake128_16(&[0xa3u8; 200]), expected);
 }
+
+// ── block index ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_greedy_block_index_matches_hash_table() {
+    let r = b"the quick brown fox jumps over the lazy dog, and then the quick brown fox jumps again";
+    let v = b"a quick brown fox jumps over the very lazy dog, and then a quick brown fox jumps high";
+
+    let baseline = diff_greedy(r, v, &opts(8));
+    let via_block_index = diff_greedy(
+        r,
+        v,
+        &DiffOptions { use_block_index: true, ..opts(8) },
+    );
+    assert_eq!(baseline, via_block_index);
+}
+
+#[test]
+fn test_greedy_block_index_agrees_across_random_keys() {
+    // Each diff_greedy(..., use_block_index: true) call draws its own random
+    // key internally; repeated calls must still agree with each other, since
+    // the key only changes lookup order/collisions, never which candidates
+    // are (after byte-verification) accepted as matches.
+    let r = b"0123456789abcdef0123456789abcdef0123456789abcdef";
+    let v = b"xx0123456789abcdefyy0123456789abcdefzz0123456789abcdef";
+
+    let o = DiffOptions { use_block_index: true, ..opts(6) };
+    let first = diff_greedy(r, v, &o);
+    for _ in 0..5 {
+        assert_eq!(diff_greedy(r, v, &o), first);
+    }
+}
+
+#[test]
+fn test_block_index_query_independent_of_key() {
+    let r = b"mississippi river delta mississippi basin";
+    let v = b"ssi";
+    let window = 3;
+
+    let mut by_key: Vec<Vec<usize>> = Vec::new();
+    for key in [0u64, 1, 42, u64::MAX] {
+        let idx = BlockIndex::build_with_key(r, window, key);
+        let mut cands = idx.query(v, 0).to_vec();
+        cands.sort();
+        by_key.push(cands);
+    }
+    assert!(by_key.windows(2).all(|pair| pair[0] == pair[1]));
+}
+
+// ── iterator-based streaming codec ──────────────────────────────────────
+
+#[test]
+fn test_delta_reader_matches_decode_delta() {
+    let r = b"the quick brown fox jumps over the lazy dog";
+    let v = b"a quick brown fox jumps over a very lazy dog";
+
+    let cmds = diff_greedy(r, v, &opts(8));
+    let placed = place_commands(&cmds);
+    let delta = encode_delta(&placed, false, output_size(&cmds), &shake128_16(r), &shake128_16(v), Compressor::None);
+
+    let (expected, is_ip, version_size, src_hash, dst_hash) = decode_delta(&delta).unwrap();
+
+    let reader = DeltaReader::new(delta.as_slice()).unwrap();
+    assert_eq!(reader.inplace, is_ip);
+    assert_eq!(reader.version_size, version_size);
+    assert_eq!(reader.src_hash, src_hash);
+    assert_eq!(reader.dst_hash, dst_hash);
+    let streamed: Vec<PlacedCommand> = reader.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(streamed, expected);
+}
+
+#[test]
+fn test_delta_reader_truncated_command_stream_is_unexpected_eof() {
+    let r = b"hello world";
+    let v = b"hello there world";
+    let cmds = diff_greedy(r, v, &opts(4));
+    let placed = place_commands(&cmds);
+    let mut delta = encode_delta(&placed, false, output_size(&cmds), &shake128_16(r), &shake128_16(v), Compressor::None);
+    delta.truncate(delta.len() - 1);
+
+    match DeltaReader::new(delta.as_slice()) {
+        Err(DeltaError::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof building a truncated DeltaReader, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_delta_writer_roundtrips_through_decode_delta() {
+    let r = b"one two three four five";
+    let v = b"zero one two three four six";
+    let cmds = diff_greedy(r, v, &opts(4));
+    let placed = place_commands(&cmds);
+
+    let mut buf = Vec::new();
+    let mut writer = DeltaWriter::new(
+        &mut buf,
+        false,
+        output_size(&cmds),
+        &shake128_16(r),
+        &shake128_16(v),
+        Compressor::None,
+    );
+    for cmd in &placed {
+        writer.push(cmd);
+    }
+    writer.finish().unwrap();
+
+    let (decoded, is_ip, version_size, src_hash, dst_hash) = decode_delta(&buf).unwrap();
+    assert!(!is_ip);
+    assert_eq!(version_size, output_size(&cmds));
+    assert_eq!(src_hash, shake128_16(r));
+    assert_eq!(dst_hash, shake128_16(v));
+    assert_eq!(decoded, placed);
+
+    let mut out = vec![0u8; version_size];
+    apply_placed_to(r, &decoded, &mut out);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_delta_writer_matches_encode_delta() {
+    let r = b"reference data for the writer test";
+    let v = b"version data for the writer test too";
+    let cmds = diff_greedy(r, v, &opts(4));
+    let placed = place_commands(&cmds);
+
+    let expected = encode_delta(&placed, false, output_size(&cmds), &shake128_16(r), &shake128_16(v), Compressor::None);
+
+    let mut buf = Vec::new();
+    let mut writer = DeltaWriter::new(
+        &mut buf,
+        false,
+        output_size(&cmds),
+        &shake128_16(r),
+        &shake128_16(v),
+        Compressor::None,
+    );
+    for cmd in &placed {
+        writer.push(cmd);
+    }
+    writer.finish().unwrap();
+
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn test_placed_run_roundtrips_through_encode_delta() {
+    let placed = vec![
+        PlacedCommand::Add { dst: 0, data: b"head".to_vec() },
+        PlacedCommand::Run { dst: 4, byte: b'x', length: 40 },
+        PlacedCommand::Add { dst: 44, data: b"tail".to_vec() },
+    ];
+    let version_size = 48;
+    let r = b"unrelated reference";
+    let src_hash = shake128_16(r);
+    let dst_hash = [0u8; 16];
+
+    let encoded = encode_delta(&placed, false, version_size, &src_hash, &dst_hash, Compressor::None);
+    let (decoded, is_ip, decoded_size, decoded_src, decoded_dst) = decode_delta(&encoded).unwrap();
+    assert!(!is_ip);
+    assert_eq!(decoded_size, version_size);
+    assert_eq!(decoded_src, src_hash);
+    assert_eq!(decoded_dst, dst_hash);
+    assert_eq!(decoded, placed);
+
+    let mut out = vec![0u8; version_size];
+    apply_placed_to(r, &decoded, &mut out);
+    let mut expected = b"head".to_vec();
+    expected.extend(std::iter::repeat(b'x').take(40));
+    expected.extend(b"tail");
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn test_greedy_emits_run_for_long_identical_byte_region() {
+    let r = b"nothing in common here at all";
+    let mut v = b"prefix:".to_vec();
+    v.extend(std::iter::repeat(b'z').take(DELTA_MIN_RUN_LENGTH + 4));
+    v.extend(b":suffix");
+
+    let cmds = diff_greedy(r, &v, &opts(4));
+    assert!(
+        cmds.iter().any(|c| matches!(c, Command::Run { byte, length } if *byte == b'z' && *length >= DELTA_MIN_RUN_LENGTH)),
+        "expected a Run command for the long identical-byte region, got {:?}",
+        cmds
+    );
+
+    let placed = place_commands(&cmds);
+    let mut out = vec![0u8; output_size(&cmds)];
+    apply_placed_to(r, &placed, &mut out);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_short_identical_byte_region_stays_add() {
+    let r = b"nothing in common here at all";
+    let mut v = b"prefix:".to_vec();
+    v.extend(std::iter::repeat(b'z').take(DELTA_MIN_RUN_LENGTH - 1));
+    v.extend(b":suffix");
+
+    let cmds = diff_greedy(r, &v, &opts(4));
+    assert!(
+        !cmds.iter().any(|c| matches!(c, Command::Run { .. })),
+        "a short identical-byte run below the threshold should not become a Run: {:?}",
+        cmds
+    );
+
+    let placed = place_commands(&cmds);
+    let mut out = vec![0u8; output_size(&cmds)];
+    apply_placed_to(r, &placed, &mut out);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_delta_writer_roundtrips_run_command() {
+    let placed = vec![PlacedCommand::Run { dst: 0, byte: 0xAB, length: 16 }];
+    let r: &[u8] = b"";
+    let v = vec![0xABu8; 16];
+
+    let mut buf = Vec::new();
+    let mut writer = DeltaWriter::new(&mut buf, false, v.len(), &shake128_16(r), &shake128_16(&v), Compressor::None);
+    for cmd in &placed {
+        writer.push(cmd);
+    }
+    writer.finish().unwrap();
+
+    let (decoded, _, version_size, _, _) = decode_delta(&buf).unwrap();
+    assert_eq!(decoded, placed);
+
+    let out = apply_delta_checked(r, &buf).unwrap();
+    assert_eq!(out.len(), version_size);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_placed_summary_reports_run_counts_and_bytes() {
+    let placed = vec![
+        PlacedCommand::Add { dst: 0, data: b"abc".to_vec() },
+        PlacedCommand::Run { dst: 3, byte: b'q', length: 25 },
+        PlacedCommand::Copy { src: 0, dst: 28, length: 3 },
+    ];
+    let stats = placed_summary(&placed);
+    assert_eq!(stats.num_runs, 1);
+    assert_eq!(stats.run_bytes, 25);
+    assert_eq!(stats.num_adds, 1);
+    assert_eq!(stats.num_copies, 1);
+    assert_eq!(stats.total_output_bytes, 3 + 25 + 3);
+}
+
+// ── direct command stream (ToWriter / FromReader) ────────────────────────
+
+#[test]
+fn test_command_stream_roundtrip_via_writer_and_reader() {
+    let r = b"the quick brown fox jumps over the lazy dog";
+    let v = b"a quick brown fox jumps over a very lazy dog";
+    let cmds = diff_greedy(r, v, &opts(8));
+    let placed = place_commands(&cmds);
+
+    let mut buf = Vec::new();
+    for cmd in &placed {
+        cmd.write_to(&mut buf).unwrap();
+    }
+    write_end(&mut buf).unwrap();
+
+    let streamed: Vec<PlacedCommand> =
+        CommandReader::new(buf.as_slice()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(streamed, placed);
+
+    let mut out = vec![0u8; output_size(&cmds)];
+    apply_placed_to(r, &streamed, &mut out);
+    assert_eq!(out, v);
+}
+
+#[test]
+fn test_command_stream_read_from_matches_command_reader() {
+    let placed = vec![
+        PlacedCommand::Add { dst: 0, data: b"head".to_vec() },
+        PlacedCommand::Run { dst: 4, byte: b'x', length: 40 },
+        PlacedCommand::CopyOut { src: 0, dst: 44, length: 4 },
+    ];
+    let mut buf = Vec::new();
+    for cmd in &placed {
+        cmd.write_to(&mut buf).unwrap();
+    }
+    write_end(&mut buf).unwrap();
+
+    let mut cursor = buf.as_slice();
+    let mut via_from_reader = Vec::new();
+    loop {
+        // Mirrors what CommandReader does internally: peek the opcode, stop
+        // at END.
+        let mut opcode = [0u8; 1];
+        use std::io::Read;
+        let n = cursor.read(&mut opcode).unwrap();
+        assert_eq!(n, 1, "stream should still hold the END opcode");
+        if opcode[0] == 0 {
+            break;
+        }
+        let mut with_opcode = (&opcode[..]).chain(&mut cursor);
+        via_from_reader.push(PlacedCommand::read_from(&mut with_opcode).unwrap());
+    }
+    assert_eq!(via_from_reader, placed);
+}
+
+#[test]
+fn test_command_stream_truncated_is_unexpected_eof() {
+    let placed = vec![PlacedCommand::Add { dst: 0, data: b"hello".to_vec() }];
+    let mut buf = Vec::new();
+    for cmd in &placed {
+        cmd.write_to(&mut buf).unwrap();
+    }
+    write_end(&mut buf).unwrap();
+    buf.truncate(buf.len() - 2); // cut into the Add's literal payload
+
+    let results: Vec<_> = CommandReader::new(buf.as_slice()).collect();
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0], Err(DeltaError::UnexpectedEof)));
+}
+
+#[test]
+fn test_command_stream_unknown_opcode_is_invalid_format() {
+    let buf = vec![0xffu8, 0, 0, 0, 0]; // bogus opcode, never defined
+    let results: Vec<_> = CommandReader::new(buf.as_slice()).collect();
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0], Err(DeltaError::InvalidFormat(_))));
+}
+
+#[test]
+fn test_command_stream_clean_eof_without_end_opcode_stops_cleanly() {
+    // A reader that forgot (or had no chance) to call write_end should still
+    // terminate at a clean EOF rather than erroring.
+    let placed = vec![PlacedCommand::Add { dst: 0, data: b"abc".to_vec() }];
+    let mut buf = Vec::new();
+    for cmd in &placed {
+        cmd.write_to(&mut buf).unwrap();
+    }
+    let streamed: Vec<PlacedCommand> =
+        CommandReader::new(buf.as_slice()).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(streamed, placed);
+}
+
+#[test]
+fn test_placed_summary_from_matches_placed_summary() {
+    let placed = vec![
+        PlacedCommand::Add { dst: 0, data: b"abc".to_vec() },
+        PlacedCommand::Run { dst: 3, byte: b'q', length: 25 },
+        PlacedCommand::Copy { src: 0, dst: 28, length: 3 },
+    ];
+    let mut buf = Vec::new();
+    for cmd in &placed {
+        cmd.write_to(&mut buf).unwrap();
+    }
+    write_end(&mut buf).unwrap();
+
+    let expected = placed_summary(&placed);
+    let streamed = placed_summary_from(buf.as_slice()).unwrap();
+    assert_eq!(streamed.num_commands, expected.num_commands);
+    assert_eq!(streamed.num_copies, expected.num_copies);
+    assert_eq!(streamed.num_adds, expected.num_adds);
+    assert_eq!(streamed.num_runs, expected.num_runs);
+    assert_eq!(streamed.copy_bytes, expected.copy_bytes);
+    assert_eq!(streamed.add_bytes, expected.add_bytes);
+    assert_eq!(streamed.run_bytes, expected.run_bytes);
+    assert_eq!(streamed.total_output_bytes, expected.total_output_bytes);
+}